@@ -1,7 +1,55 @@
 <?xml version="1.0" encoding="UTF-8"?>
-<tileset version="1.2" tiledversion="1.2.4" name="test" tilewidth="32" tileheight="32" tilecount="1" columns="0">
+<tileset version="1.2" tiledversion="1.2.4" name="test" tilewidth="32" tileheight="32" tilecount="9" columns="0">
  <grid orientation="orthogonal" width="1" height="1"/>
  <tile id="1">
   <image width="32" height="32" source="../sprites/brick_small.png"/>
  </tile>
+ <tile id="2" type="ladder">
+  <properties>
+   <property name="climbable" value="ladder"/>
+  </properties>
+  <image width="32" height="32" source="../sprites/ladder.png"/>
+ </tile>
+ <tile id="3" type="rope">
+  <properties>
+   <property name="climbable" value="rope"/>
+  </properties>
+  <image width="32" height="32" source="../sprites/rope.png"/>
+ </tile>
+ <tile id="4" type="water">
+  <properties>
+   <property name="liquid" value="water"/>
+  </properties>
+  <image width="32" height="32" source="../sprites/water.png"/>
+ </tile>
+ <tile id="5" type="ice">
+  <properties>
+   <property name="material" value="ice"/>
+  </properties>
+  <image width="32" height="32" source="../sprites/ice.png"/>
+ </tile>
+ <tile id="6" type="bouncy">
+  <properties>
+   <property name="material" value="bouncy"/>
+  </properties>
+  <image width="32" height="32" source="../sprites/bouncy.png"/>
+ </tile>
+ <tile id="7" type="conveyor">
+  <properties>
+   <property name="material" value="conveyor"/>
+  </properties>
+  <image width="32" height="32" source="../sprites/conveyor.png"/>
+ </tile>
+ <tile id="8" type="smooth">
+  <properties>
+   <property name="material" value="smooth"/>
+  </properties>
+  <image width="32" height="32" source="../sprites/smooth.png"/>
+ </tile>
+ <tile id="9" type="spikes">
+  <properties>
+   <property name="damage" type="int" value="1"/>
+  </properties>
+  <image width="32" height="32" source="../sprites/spikes.png"/>
+ </tile>
 </tileset>
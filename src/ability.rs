@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+
+// Prefix of map properties that unlock abilities, ex. "ability.dash".
+const UNLOCK_PREFIX: &str = "ability.";
+
+// Which abilities a character has unlocked. How the abilities feel is set in
+// the movement profile.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Abilities {
+	pub dash: bool,
+	// Number of extra jumps that can be done in the air.
+	pub air_jumps: u32,
+	pub ground_pound: bool,
+}
+
+impl Abilities {
+	// Unlock the abilities a map grants through its properties on top of the unlocked ones.
+	pub fn with_map(&self, map_properties: &HashMap<String, String>) -> Abilities {
+		let mut abilities = self.clone();
+		for (name, value) in map_properties {
+			if !name.starts_with(UNLOCK_PREFIX) {
+				continue
+			}
+			match &name[UNLOCK_PREFIX.len()..] {
+				"dash" => abilities.dash |= value == "true",
+				"ground_pound" => abilities.ground_pound |= value == "true",
+				"air_jumps" => match value.parse() {
+					Ok(jumps) => abilities.air_jumps = abilities.air_jumps.max(jumps),
+					Err(_) => println!("Map property {:?} is not a number!", name),
+				},
+				_ => println!("Unknown ability {:?} in map properties", name),
+			}
+		}
+		abilities
+	}
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use crate::vector::Vec2;
+use crate::METER_IN_PIXELS;
+
+// Decides where a mob wants to go, the mob itself does the moving.
+pub enum Controller {
+	// Walk back and forth, turning around at walls and edges.
+	Patrol,
+	// Patrol until the player comes within range, then run after them.
+	Chase { range: f64 },
+	// Stand still and jump every interval, towards the player if they are in range.
+	Jumper { range: f64, interval: f64, timer: f64 },
+	// Ignore gravity and hover around home, swooping at the player in range.
+	Flyer { range: f64, home: Vec2, time: f64 },
+}
+
+// What the mob knows about its surroundings this update.
+pub struct Senses {
+	pub pos: Vec2,
+	pub look_dir: f64,
+	pub on_ground: bool,
+	// Whether there is something to stand on just in front of the mob.
+	pub ground_ahead: bool,
+	pub wall_ahead: bool,
+	pub target: Option<Vec2>,
+}
+
+pub struct Intent {
+	// Direction to run in, -1 is left and 1 is right.
+	pub run: f64,
+	pub jump: bool,
+	// Direction to fly in, only used by flying mobs.
+	pub fly: Option<Vec2>,
+}
+
+impl Controller {
+	// Read the "ai" property of a map object, ranges are given in tiles.
+	pub fn from_properties(properties: &HashMap<String, String>, home: Vec2) -> Controller {
+		let property = |name: &str, default: f64| properties.get(name).and_then(|v| v.parse().ok()).unwrap_or(default);
+		let range = property("range", 6.)*METER_IN_PIXELS;
+		match properties.get("ai").map(|a| a.as_str()) {
+			Some("chase") => Controller::Chase { range },
+			Some("jumper") => Controller::Jumper { range, interval: property("interval", 1.5), timer: 0. },
+			Some("flyer") => Controller::Flyer { range, home, time: 0. },
+			Some("patrol") | None => Controller::Patrol,
+			Some(other) => {
+				println!("Unknown ai {:?}, using patrol", other);
+				Controller::Patrol
+			},
+		}
+	}
+	pub fn think(&mut self, senses: &Senses, dt: f64) -> Intent {
+		let mut intent = Intent { run: 0., jump: false, fly: None };
+		match self {
+			Controller::Patrol => {
+				intent.run = patrol(senses);
+			},
+			Controller::Chase { range } => {
+				match in_range(senses, *range) {
+					Some(target) => {
+						let dx = target.x-senses.pos.x;
+						// Stop right below or above the player instead of jittering.
+						intent.run = if dx.abs() < 4. { 0. } else { dx.signum() };
+						intent.jump = senses.on_ground && senses.wall_ahead;
+					},
+					None => intent.run = patrol(senses),
+				}
+			},
+			Controller::Jumper { range, interval, timer } => {
+				if senses.on_ground {
+					*timer -= dt;
+					if *timer <= 0. {
+						*timer = *interval;
+						intent.jump = true;
+					}
+				}
+				// Only steer while in the air so it hops instead of walking.
+				if !senses.on_ground || intent.jump {
+					intent.run = match in_range(senses, *range) {
+						Some(target) => (target.x-senses.pos.x).signum(),
+						None => if senses.wall_ahead { -senses.look_dir } else { senses.look_dir },
+					};
+				}
+			},
+			Controller::Flyer { range, home, time } => {
+				*time += dt;
+				let goal = match in_range(senses, *range) {
+					Some(target) => target,
+					// Bob around a figure eight while idle.
+					None => Vec2::new(home.x+(*time*0.8).sin()*METER_IN_PIXELS*2.,
+					                  home.y+(*time*1.6).sin()*METER_IN_PIXELS*0.5),
+				};
+				let (dx, dy) = (goal.x-senses.pos.x, goal.y-senses.pos.y);
+				let length = (dx*dx+dy*dy).sqrt();
+				if length > 1. {
+					intent.fly = Some(Vec2::new(dx/length, dy/length));
+					intent.run = dx.signum();
+				}else {
+					intent.fly = Some(Vec2::new(0., 0.));
+				}
+			},
+		}
+		intent
+	}
+}
+
+fn patrol(senses: &Senses) -> f64 {
+	if senses.on_ground && (senses.wall_ahead || !senses.ground_ahead) {
+		-senses.look_dir
+	}else {
+		senses.look_dir
+	}
+}
+
+fn in_range(senses: &Senses, range: f64) -> Option<Vec2> {
+	let target = senses.target?;
+	let (dx, dy) = (target.x-senses.pos.x, target.y-senses.pos.y);
+	if dx*dx+dy*dy <= range*range { Some(target) } else { None }
+}
@@ -0,0 +1,177 @@
+use std::collections::{BTreeMap, HashMap};
+use serde::Deserialize;
+
+// What transitions between animation states depend on. The animate system
+// sets the ones read from the components of an entity, controllers the rest.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Flag {
+	OnGround,
+	// Moving sideways faster than 0.1 m/s.
+	Moving,
+	Rising,
+	Falling,
+	OnWall,
+	Climbing,
+	Swimming,
+	Dashing,
+	GroundPounding,
+	// Only set during the update the player jumped in the air.
+	AirJump,
+	// The clip of a one-shot state has played to its end.
+	Finished,
+}
+
+impl Flag {
+	fn from_name(name: &str) -> Option<Flag> {
+		Some(match name {
+			"on_ground" => Flag::OnGround,
+			"moving" => Flag::Moving,
+			"rising" => Flag::Rising,
+			"falling" => Flag::Falling,
+			"on_wall" => Flag::OnWall,
+			"climbing" => Flag::Climbing,
+			"swimming" => Flag::Swimming,
+			"dashing" => Flag::Dashing,
+			"ground_pounding" => Flag::GroundPounding,
+			"air_jump" => Flag::AirJump,
+			"finished" => Flag::Finished,
+			_ => return None,
+		})
+	}
+	pub fn bit(self) -> u32 {
+		1 << self as u32
+	}
+}
+
+// The [animator] table of a prefab.
+#[derive(Deserialize, Clone)]
+pub struct AnimatorDef {
+	initial: String,
+	// Checked in every state before the transitions of the state.
+	#[serde(default)]
+	any: Vec<TransitionDef>,
+	states: BTreeMap<String, StateDef>,
+}
+
+#[derive(Deserialize, Clone)]
+struct StateDef {
+	// Clip of the sprite sheet, named like the state if not given.
+	clip: Option<String>,
+	// One-shot states stay on their last frame and set "finished".
+	#[serde(default = "looping")]
+	looping: bool,
+	#[serde(default)]
+	transitions: Vec<TransitionDef>,
+	#[serde(default)]
+	events: Vec<EventDef>,
+}
+
+fn looping() -> bool {
+	true
+}
+
+// Taken when all the flags in "when" are set, or not set with a "!" in front.
+#[derive(Deserialize, Clone)]
+struct TransitionDef {
+	to: String,
+	#[serde(default)]
+	when: Vec<String>,
+}
+
+// Sent when the clip reaches the frame, counted from 0 in the clip.
+#[derive(Deserialize, Clone)]
+struct EventDef {
+	frame: usize,
+	name: String,
+}
+
+struct Transition {
+	to: usize,
+	// The flags checked, and the values they need to have.
+	mask: u32,
+	expected: u32,
+}
+
+pub struct State {
+	pub name: String,
+	pub clip: String,
+	pub looping: bool,
+	transitions: Vec<Transition>,
+	events: Vec<EventDef>,
+}
+
+impl State {
+	// Names of the events on the frame at the position in the clip.
+	pub fn events(&self, position: usize) -> impl Iterator<Item = &str> {
+		self.events.iter().filter(move |e| e.frame == position).map(|e| e.name.as_str())
+	}
+}
+
+// The animation states of a kind of entity and the transitions between them,
+// shared by every entity spawned from the same prefab. States are numbered in
+// the order of their names.
+pub struct Animator {
+	pub states: Vec<State>,
+	pub initial: usize,
+	any: Vec<Transition>,
+}
+
+impl Animator {
+	pub fn new(def: &AnimatorDef) -> Result<Animator, String> {
+		let index: HashMap<&str, usize> = def.states.keys().enumerate().map(|(i, name)| (name.as_str(), i)).collect();
+		let state = |name: &str| index.get(name).cloned().ok_or_else(|| format!("unknown state {:?}", name));
+		let transitions = |defs: &Vec<TransitionDef>| -> Result<Vec<Transition>, String> {
+			defs.iter().map(|def| {
+				let (mut mask, mut expected) = (0, 0);
+				for condition in &def.when {
+					let (name, value) = match condition.starts_with('!') {
+						true => (&condition[1..], false),
+						false => (condition.as_str(), true),
+					};
+					let flag = Flag::from_name(name).ok_or_else(|| format!("unknown condition {:?}", name))?;
+					mask |= flag.bit();
+					if value {
+						expected |= flag.bit();
+					}
+				}
+				Ok(Transition { to: state(&def.to)?, mask, expected })
+			}).collect()
+		};
+		let mut states = Vec::new();
+		for (name, def) in &def.states {
+			states.push(State {
+				name: name.clone(),
+				clip: def.clip.clone().unwrap_or_else(|| name.clone()),
+				looping: def.looping,
+				transitions: transitions(&def.transitions)?,
+				events: def.events.clone(),
+			});
+		}
+		Ok(Animator {
+			states,
+			initial: state(&def.initial)?,
+			any: transitions(&def.any)?,
+		})
+	}
+	// A single looping state for entities without an animator, showing the
+	// first clip of their sheet.
+	pub fn single() -> Animator {
+		Animator {
+			states: vec![State {
+				name: String::from("default"),
+				clip: String::new(),
+				looping: true,
+				transitions: Vec::new(),
+				events: Vec::new(),
+			}],
+			initial: 0,
+			any: Vec::new(),
+		}
+	}
+	// The state to switch to from the current one, if any.
+	pub fn next(&self, state: usize, flags: u32) -> Option<usize> {
+		self.any.iter().chain(&self.states[state].transitions)
+			.find(|t| t.to != state && flags & t.mask == t.expected)
+			.map(|t| t.to)
+	}
+}
@@ -0,0 +1,259 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::SystemTime;
+use image::RgbaImage;
+
+use crate::sheet::SpriteSheet;
+
+// Width and height of atlas pages, bigger images get a page of their own.
+pub const PAGE_SIZE: u32 = 2048;
+// Pixels around every image, filled with its edges so scaled neighbours don't
+// bleed into it.
+const PADDING: u32 = 1;
+
+// Where a texture is in the atlas.
+#[derive(Clone, Copy)]
+pub struct Region {
+	pub page: usize,
+	// x, y, width and height in pixels of the page.
+	pub rect: [f64; 4],
+	pub page_size: [f64; 2],
+}
+
+impl Region {
+	pub fn width(&self) -> f64 {
+		self.rect[2]
+	}
+	pub fn height(&self) -> f64 {
+		self.rect[3]
+	}
+	// Texture coordinates of the corners of a part of the texture, given in
+	// pixels of the texture. Left, top, right and bottom.
+	pub fn uv(&self, src: [f64; 4]) -> [f32; 4] {
+		let (x, y) = (self.rect[0]+src[0], self.rect[1]+src[1]);
+		[(x/self.page_size[0]) as f32, (y/self.page_size[1]) as f32,
+		 ((x+src[2])/self.page_size[0]) as f32, ((y+src[3])/self.page_size[1]) as f32]
+	}
+}
+
+// A texture shared by everything drawn with it. The region changes if the
+// texture is reloaded with a different size.
+#[derive(Clone)]
+pub struct TextureHandle(Rc<Cell<Region>>);
+
+impl TextureHandle {
+	pub fn region(&self) -> Region {
+		self.0.get()
+	}
+}
+
+// An atlas page. The pixels are kept so renderers can upload the page again
+// when its version changes.
+pub struct Page {
+	pub image: RgbaImage,
+	pub version: u32,
+	shelves: Vec<Shelf>,
+}
+
+// A row of images filling the page from the left.
+struct Shelf {
+	y: u32,
+	height: u32,
+	used: u32,
+}
+
+impl Page {
+	fn new(width: u32, height: u32) -> Page {
+		Page {
+			image: RgbaImage::new(width, height),
+			version: 0,
+			shelves: Vec::new(),
+		}
+	}
+	// Find room for an image, None if the page is full.
+	fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+		let (w, h) = (width+2*PADDING, height+2*PADDING);
+		let (page_w, page_h) = self.image.dimensions();
+		// The lowest shelf the image fits on, so small images don't use up tall shelves.
+		let shelf = self.shelves.iter_mut()
+			.filter(|s| s.height >= h && s.used+w <= page_w)
+			.min_by_key(|s| s.height);
+		if let Some(shelf) = shelf {
+			shelf.used += w;
+			return Some((shelf.used-w+PADDING, shelf.y+PADDING))
+		}
+		let y = self.shelves.last().map(|s| s.y+s.height).unwrap_or(0);
+		if y+h > page_h || w > page_w {
+			return None
+		}
+		self.shelves.push(Shelf { y, height: h, used: w });
+		Some((PADDING, y+PADDING))
+	}
+	fn blit(&mut self, image: &RgbaImage, x: u32, y: u32) {
+		let (w, h) = image.dimensions();
+		let padding = PADDING as i64;
+		for dy in -padding..h as i64+padding {
+			for dx in -padding..w as i64+padding {
+				let source = (dx.max(0).min(w as i64-1) as u32, dy.max(0).min(h as i64-1) as u32);
+				let pixel = *image.get_pixel(source.0, source.1);
+				self.image.put_pixel((x as i64+dx) as u32, (y as i64+dy) as u32, pixel);
+			}
+		}
+		self.version += 1;
+	}
+}
+
+pub struct TextureStats {
+	pub textures: usize,
+	pub pages: usize,
+	// Size of all atlas pages, in bytes.
+	pub bytes: usize,
+	// How many times a texture was asked for, and how many of those were loaded from disk.
+	pub requests: usize,
+	pub loads: usize,
+	pub reloads: usize,
+}
+
+struct Loaded {
+	handle: TextureHandle,
+	modified: Option<SystemTime>,
+}
+
+// Loads every texture once, keyed by its path, and packs them into atlas
+// pages. Textures changed on disk are reloaded in place, so everything drawn
+// with them shows the new image.
+pub struct Textures {
+	pages: Vec<Page>,
+	loaded: HashMap<String, Loaded>,
+	sheets: HashMap<String, Option<Rc<SpriteSheet>>>,
+	requests: usize,
+	reloads: usize,
+	layout: u32,
+}
+
+impl Textures {
+	pub fn new() -> Textures {
+		Textures {
+			pages: Vec::new(),
+			loaded: HashMap::new(),
+			sheets: HashMap::new(),
+			requests: 0,
+			reloads: 0,
+			layout: 0,
+		}
+	}
+	pub fn load(&mut self, path: &str) -> TextureHandle {
+		self.requests += 1;
+		if let Some(loaded) = self.loaded.get(path) {
+			return loaded.handle.clone()
+		}
+		let modified = modified_time(path);
+		let image = read(path).unwrap_or_else(|e| panic!("Could not load texture {:?}: {}", path, e));
+		let handle = TextureHandle(Rc::new(Cell::new(self.pack(&image))));
+		self.loaded.insert(String::from(path), Loaded { handle: handle.clone(), modified });
+		handle
+	}
+	// Sprite sheets are also loaded once, a sheet that can't be loaded is only
+	// reported the first time.
+	pub fn load_sheet(&mut self, path: &str) -> Option<Rc<SpriteSheet>> {
+		if let Some(sheet) = self.sheets.get(path) {
+			return sheet.clone()
+		}
+		let sheet = match SpriteSheet::load(path, self) {
+			Ok(sheet) => Some(Rc::new(sheet)),
+			Err(e) => {
+				println!("Could not load sprite sheet {:?}: {}", path, e);
+				None
+			},
+		};
+		self.sheets.insert(String::from(path), sheet.clone());
+		sheet
+	}
+	// Reload the textures that have changed since they were loaded. A file that
+	// can't be read, ex. because it's still being exported, keeps the old image
+	// and is tried again next time.
+	pub fn reload_changed(&mut self) {
+		let changed: Vec<String> = self.loaded.iter()
+			.filter(|(path, loaded)| modified_time(path) != loaded.modified)
+			.map(|(path, _)| path.clone())
+			.collect();
+		for path in changed {
+			let modified = modified_time(&path);
+			match read(&path) {
+				Ok(image) => {
+					let handle = self.loaded[&path].handle.clone();
+					let region = handle.region();
+					// Images keeping their size are copied over the old one.
+					if image.dimensions() == (region.width() as u32, region.height() as u32) {
+						self.pages[region.page].blit(&image, region.rect[0] as u32, region.rect[1] as u32);
+					}else {
+						handle.0.set(self.pack(&image));
+						self.layout += 1;
+					}
+					self.loaded.get_mut(&path).unwrap().modified = modified;
+					self.reloads += 1;
+					println!("\nReloaded texture {:?}", path);
+				},
+				Err(e) => println!("\nCould not reload texture {:?}: {}", path, e),
+			}
+		}
+	}
+	// Changed when a texture moves to another place in the atlas, anything
+	// keeping texture coordinates has to build them again.
+	pub fn layout(&self) -> u32 {
+		self.layout
+	}
+	pub fn pages(&self) -> &[Page] {
+		&self.pages
+	}
+	pub fn stats(&self) -> TextureStats {
+		TextureStats {
+			textures: self.loaded.len(),
+			pages: self.pages.len(),
+			bytes: self.pages.iter().map(|p| {
+				let (w, h) = p.image.dimensions();
+				w as usize*h as usize*4
+			}).sum(),
+			requests: self.requests,
+			loads: self.loaded.len(),
+			reloads: self.reloads,
+		}
+	}
+	// Put the image on the first page with room for it, or on a new page.
+	fn pack(&mut self, image: &RgbaImage) -> Region {
+		let (w, h) = image.dimensions();
+		let found = self.pages.iter_mut().enumerate().find_map(|(i, page)| page.allocate(w, h).map(|pos| (i, pos)));
+		let (page, (x, y)) = match found {
+			Some(found) => found,
+			None => {
+				let mut page = Page::new(PAGE_SIZE.max(w+2*PADDING), PAGE_SIZE.max(h+2*PADDING));
+				let pos = page.allocate(w, h).unwrap();
+				self.pages.push(page);
+				(self.pages.len()-1, pos)
+			},
+		};
+		self.pages[page].blit(image, x, y);
+		let (page_w, page_h) = self.pages[page].image.dimensions();
+		Region {
+			page,
+			rect: [x as f64, y as f64, w as f64, h as f64],
+			page_size: [page_w as f64, page_h as f64],
+		}
+	}
+}
+
+fn read(path: &str) -> Result<RgbaImage, String> {
+	image::open(path).map(|i| i.to_rgba()).map_err(|e| e.to_string())
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+	std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl std::fmt::Display for TextureStats {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{} textures on {} atlas pages, {:.1} MB, {} of {} requests loaded from disk, {} reloads",
+		       self.textures, self.pages, self.bytes as f64/(1024.*1024.), self.loads, self.requests, self.reloads)
+	}
+}
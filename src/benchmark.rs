@@ -0,0 +1,43 @@
+use std::time::{Duration, Instant};
+
+use crate::vector::Vec2;
+
+// Renders the map with the camera moving diagonally across it instead of
+// playing, and reports how long drawing took and how many draws it needed.
+pub struct Benchmark {
+	frames: u32,
+	frame: u32,
+	time: Duration,
+}
+
+impl Benchmark {
+	pub fn new(frames: u32) -> Benchmark {
+		Benchmark {
+			frames,
+			frame: 0,
+			time: Duration::from_secs(0),
+		}
+	}
+	// Render the next frame, returns true after the last one.
+	pub fn render(&mut self, app: &mut crate::App, renderer: &mut dyn crate::render::Renderer) -> bool {
+		if self.frame == 0 {
+			app.batch.sprites = 0;
+			app.batch.draws = 0;
+		}
+		let t = self.frame as f64/self.frames as f64;
+		app.camera.center_on(Vec2::new(app.world.w*t, app.world.h*t));
+
+		let start = Instant::now();
+		app.render(renderer);
+		self.time += start.elapsed();
+		self.frame += 1;
+		if self.frame < self.frames {
+			return false
+		}
+		let frames = self.frames as f64;
+		let time = self.time.as_secs() as f64+self.time.subsec_nanos() as f64/1e9;
+		println!("Rendered {} frames of {:?} in {:.2} s, {:.3} ms per frame", self.frames, app.level.path, time, time*1000./frames);
+		println!("{:.1} draws of {:.1} batched sprites per frame", app.batch.draws as f64/frames, app.batch.sprites as f64/frames);
+		true
+	}
+}
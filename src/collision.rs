@@ -1,85 +1,123 @@
 use crate::vector::Vec2;
-use piston_window::G2dTexture as Texture;
+use crate::asset::TextureHandle;
 
+// How a surface affects whatever touches it.
+#[derive(Clone, Copy)]
+pub struct Material {
+    // Scales grip on the ground, low values make ice.
+    pub friction: f64,
+    // Part of the speed kept when bouncing off the surface.
+    pub restitution: f64,
+    // Horizontal speed the surface carries things standing on it with, in m/s.
+    pub surface_velocity: f64,
+    // Scales wall friction, walls without grip can't be walljumped off.
+    pub wall_grip: f64,
+}
+
+// Bounces slower than this are absorbed so things can come to rest.
+const MIN_BOUNCE: f64 = 1.0;
+const MAX_BOUNCE: f64 = 15.0;
+
+impl Material {
+    pub fn new() -> Material {
+        Material {
+            friction: 1.0,
+            restitution: 0.0,
+            surface_velocity: 0.0,
+            wall_grip: 1.0,
+        }
+    }
+    // Start from the preset named by the "material" property and let the other
+    // properties override single values.
+    pub fn from_properties(properties: &std::collections::HashMap<String, String>) -> Material {
+        let mut material = Material::new();
+        match properties.get("material").map(|m| m.as_str()) {
+            Some("ice") => {
+                material.friction = 0.05;
+                material.wall_grip = 0.1;
+            },
+            Some("sticky") => material.wall_grip = 3.0,
+            Some("smooth") => material.wall_grip = 0.0,
+            Some("bouncy") => material.restitution = 1.1,
+            Some("conveyor") => material.surface_velocity = 2.0,
+            Some(name) => println!("Unknown material {:?}", name),
+            None => (),
+        }
+        let property = |name: &str, default: f64| properties.get(name).and_then(|v| v.parse().ok()).unwrap_or(default);
+        material.friction = property("friction", material.friction);
+        material.restitution = property("restitution", material.restitution);
+        material.surface_velocity = property("surface_velocity", material.surface_velocity);
+        material.wall_grip = property("wall_grip", material.wall_grip);
+        material
+    }
+    // The speed something hitting the surface at the given speed bounces back with.
+    pub fn bounce(&self, speed: f64) -> f64 {
+        let bounce = speed.abs()*self.restitution;
+        if bounce < MIN_BOUNCE { 0. } else { bounce.min(MAX_BOUNCE) }
+    }
+}
+
+#[derive(Clone)]
 pub struct Cube {
     pub size: Vec2,
     pub pos: Vec2,
-    pub friction: f64,
+    pub material: Material,
     pub rotation: f64,
-    pub color: [f64; 4],
-    pub texture: Texture,
-    pub state: crate::mob::MobState,
+    pub color: [f32; 4],
+    pub texture: TextureHandle,
 }
 
-use piston_window::RenderArgs;
-use graphics::{rectangle,Transformed};
-
 impl Cube {
     pub fn new(sx: f64, sy: f64, px: f64, py: f64, texture_path: &str,
-               mut window: &mut piston_window::PistonWindow) -> Cube {
+               textures: &mut crate::asset::Textures) -> Cube {
         let size = Vec2::new(sx, sy);
         let pos = Vec2::new(px, py);
         let velocity = Vec2::new(0., 0.);
-        let texture = crate::render::create_texture(&mut window, texture_path);
+        let texture = textures.load(texture_path);
         Cube {
             size,
             pos,
-            friction: 60.,
+            material: Material::new(),
             rotation: 0.0,
             color: [1.0, 1.0, 1.0, 1.0],
             texture,
-            state: crate::mob::MobState::new(),
         }
     }
-    pub fn render(&mut self, camera: &crate::Camera, window: &mut piston_window::PistonWindow, e: &piston::Event) {
+    pub fn render(&self, camera: &crate::Camera, batch: &mut crate::render::Batch) {
         let (x, y) = (self.pos.x, self.pos.y);
-        let (w, h) = (self.size.x, self.size.y);
+        let (w, h) = self.extent();
+        let [left, top, right, bottom] = crate::render::visible_area(camera);
         // Do the vertical check first because most of the levels are vertical
-        if y-h/2. > camera.position.y+camera.h || y+h/2. < camera.position.y ||
-           x-w/2. > camera.position.x+camera.w || x+w/2. < camera.position.x {
+        if y-h/2. > bottom || y+h/2. < top ||
+           x-w/2. > right || x+w/2. < left {
             return
         }
-        let texture = &self.texture;
-
-        let scale = &camera.zoom;
-        let offset = &camera.position;
-        let (app_w, app_h) = (&camera.w, &camera.h);
-
-        use crate::piston_window::{image,ImageSize};
-        use crate::graphics::Transformed;
-        window.draw_2d(e, |c, g, _| {
-            image(texture,
-                  c.transform
-                   .trans((x-w/2.-app_w/2.)*scale+app_w/2.-offset.x,
-                          (y-h/2.-app_h/2.)*scale+app_h/2.-offset.y)
-                   .scale((w/texture.get_width() as f64)*scale,
-                          (h/texture.get_height() as f64)*scale)
-            , g);
-        });
+        self.draw(batch);
     }
-    pub fn _render(&mut self, gl: &mut opengl_graphics::GlGraphics,
-                  camera: &crate::Camera, col: [f32; 4], args: &RenderArgs) {
+    // Draw the cube whether the camera sees it or not.
+    pub fn draw(&self, batch: &mut crate::render::Batch) {
         let (x, y) = (self.pos.x, self.pos.y);
-
-        let scale = &camera.zoom;
-        let offset = &camera.position;
-        let (app_w, app_h) = (&camera.w, &camera.h);
-
-        let square = [0.0, 0.0, self.size.x, self.size.y];
-
-        gl.draw(args.viewport(), |c, gl| {
-            let transform = c.transform
-                             .trans((x-app_w/2.)*scale+app_w/2.-offset.x,
-                                    (y-app_h/2.)*scale+app_h/2.-offset.y)
-                             .rot_rad(self.rotation)
-                             .trans((-self.size.x/2.)*scale, (-self.size.y/2.)*scale)
-                             .scale(*scale, *scale);
-            rectangle(col, square, transform, gl);
-        });
+        let (w, h) = (self.size.x, self.size.y);
+        if self.rotation == 0. {
+            batch.add(&self.texture, None, [x-w/2., y-h/2., w, h], self.color);
+        }else {
+            batch.add_rotated(&self.texture, [x-w/2., y-h/2., w, h], self.rotation, self.color);
+        }
+    }
+    // Width and height of the axis aligned box around the rotated cube.
+    pub fn extent(&self) -> (f64, f64) {
+        let (sin, cos) = self.rotation.sin_cos();
+        (self.size.x*cos.abs()+self.size.y*sin.abs(), self.size.x*sin.abs()+self.size.y*cos.abs())
+    }
+    pub fn contains(&self, point: &Vec2) -> bool {
+        // Turn the point back into the frame of the cube.
+        let (sin, cos) = self.rotation.sin_cos();
+        let (dx, dy) = (point.x-self.pos.x, point.y-self.pos.y);
+        (dx*cos+dy*sin).abs()*2. < self.size.x && (dy*cos-dx*sin).abs()*2. < self.size.y
     }
 }
 
-#[allow(dead_code)]
+#[derive(Clone, Copy)]
 pub enum Side {
     North,
     South,
@@ -92,11 +130,11 @@ const BORDER_MARGIN: f64 = 7.0;
 // Detects collisions on the side of object2 coming from object1
 // ex. if object1 falls on top of object2 then it would be a north collision
 // and we would have to run cubecollider with north as the side to detect it.
-pub fn cube_collider(object1: &crate::mob::Player, object2: &Cube, side: Side) -> (bool) {
+pub fn cube_collider(pos1: &Vec2, size1: &Vec2, object2: &Cube, side: Side) -> (bool) {
     let mut collision = false;
     
-    let (px1, sx1) = (object1.pos.x,object1.size.x);
-    let (py1, sy1) = (object1.pos.y,object1.size.y);
+    let (px1, sx1) = (pos1.x,size1.x);
+    let (py1, sy1) = (pos1.y,size1.y);
     let (px2, sx2) = (object2.pos.x,object2.size.x);
     let (py2, sy2) = (object2.pos.y,object2.size.y);
 
@@ -137,4 +175,69 @@ pub fn cube_collider(object1: &crate::mob::Player, object2: &Cube, side: Side) -
         },
     }
     collision
-}
\ No newline at end of file
+}
+// An axis aligned area in the world, positioned by its center like Cube.
+pub struct Rect {
+    pub pos: Vec2,
+    pub size: Vec2,
+}
+
+impl Rect {
+    pub fn new(pos: Vec2, size: Vec2) -> Rect {
+        Rect {
+            pos,
+            size,
+        }
+    }
+    pub fn overlaps(&self, pos: &Vec2, size: &Vec2) -> bool {
+        (self.pos.x-pos.x).abs()*2. < self.size.x+size.x &&
+        (self.pos.y-pos.y).abs()*2. < self.size.y+size.y
+    }
+    pub fn contains(&self, point: &Vec2) -> bool {
+        (self.pos.x-point.x).abs()*2. < self.size.x &&
+        (self.pos.y-point.y).abs()*2. < self.size.y
+    }
+}
+
+// A ladder or rope the player can climb, ropes keep the player at their center.
+pub struct Climbable {
+    pub area: Rect,
+    pub rope: bool,
+}
+
+// An area that hurts whatever touches it, like spikes.
+pub struct Hazard {
+    pub area: Rect,
+    pub damage: i32,
+}
+
+// A body of water or another liquid. Buoyancy is the part of gravity the
+// liquid cancels, drag is how much of the velocity is lost per second.
+pub struct Liquid {
+    pub area: Rect,
+    pub buoyancy: f64,
+    pub drag: f64,
+    pub color: [f32; 4],
+}
+
+impl Liquid {
+    pub fn new(area: Rect, properties: &std::collections::HashMap<String, String>) -> Liquid {
+        let property = |name: &str, default: f64| properties.get(name).and_then(|v| v.parse().ok()).unwrap_or(default);
+        Liquid {
+            area,
+            buoyancy: property("buoyancy", 0.8),
+            drag: property("drag", 3.0),
+            color: [0.2, 0.4, 0.9, 0.5],
+        }
+    }
+    pub fn render(&self, camera: &crate::Camera, renderer: &mut dyn crate::render::Renderer) {
+        let (x, y) = (self.area.pos.x, self.area.pos.y);
+        let (w, h) = (self.area.size.x, self.area.size.y);
+        let [left, top, right, bottom] = crate::render::visible_area(camera);
+        if y-h/2. > bottom || y+h/2. < top ||
+           x-w/2. > right || x+w/2. < left {
+            return
+        }
+        renderer.rectangle(self.color, [x-w/2., y-h/2., w, h], crate::render::view(camera));
+    }
+}
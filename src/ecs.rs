@@ -0,0 +1,315 @@
+use std::rc::Rc;
+
+use crate::animator::{Animator, Flag};
+use crate::asset::TextureHandle;
+use crate::health::{Damage, Health};
+use crate::mob::{Mob, Player};
+use crate::sheet::{Frame, SpriteSheet};
+use crate::vector::Vec2;
+
+// An index into the component storages, the generation tells apart entities
+// that reuse the index of a despawned one.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Entity {
+	index: u32,
+	generation: u32,
+}
+
+impl Entity {
+	// Packs the entity into a single number, how scripts refer to entities.
+	pub fn to_bits(self) -> u64 {
+		(self.generation as u64) << 32 | self.index as u64
+	}
+	pub fn from_bits(bits: u64) -> Entity {
+		Entity {
+			index: bits as u32,
+			generation: (bits >> 32) as u32,
+		}
+	}
+}
+
+// Components of a single type, stored by entity index.
+pub struct Storage<T> {
+	items: Vec<Option<(u32, T)>>,
+}
+
+impl<T> Storage<T> {
+	pub fn new() -> Storage<T> {
+		Storage {
+			items: Vec::new(),
+		}
+	}
+	pub fn insert(&mut self, entity: Entity, component: T) {
+		let index = entity.index as usize;
+		while self.items.len() <= index {
+			self.items.push(None);
+		}
+		self.items[index] = Some((entity.generation, component));
+	}
+	pub fn remove(&mut self, entity: Entity) -> Option<T> {
+		match self.items.get_mut(entity.index as usize) {
+			Some(item) if item.as_ref().map(|(g, _)| *g) == Some(entity.generation) => item.take().map(|(_, c)| c),
+			_ => None,
+		}
+	}
+	pub fn get(&self, entity: Entity) -> Option<&T> {
+		match self.items.get(entity.index as usize) {
+			Some(Some((generation, component))) if *generation == entity.generation => Some(component),
+			_ => None,
+		}
+	}
+	pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+		match self.items.get_mut(entity.index as usize) {
+			Some(Some((generation, component))) if *generation == entity.generation => Some(component),
+			_ => None,
+		}
+	}
+	pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+		self.items.iter().enumerate().filter_map(|(index, item)| {
+			item.as_ref().map(|(generation, component)| (Entity { index: index as u32, generation: *generation }, component))
+		})
+	}
+	pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+		self.items.iter_mut().enumerate().filter_map(|(index, item)| {
+			item.as_mut().map(|(generation, component)| (Entity { index: index as u32, generation: *generation }, component))
+		})
+	}
+}
+
+// Where an entity is, how big it is and which way it faces, 1 is right and -1 left.
+#[derive(Clone, Copy)]
+pub struct Transform {
+	pub pos: Vec2,
+	pub size: Vec2,
+	pub facing: f64,
+}
+
+impl Transform {
+	pub fn new(pos: Vec2, size: Vec2) -> Transform {
+		Transform {
+			pos,
+			size,
+			facing: 1.,
+		}
+	}
+}
+
+// In meters per second.
+pub type Velocity = Vec2;
+
+pub struct Collider {
+	// Which projectile layer the entity is on.
+	pub layer: u8,
+	// Only true while standing on something.
+	pub on_ground: bool,
+}
+
+pub struct Sprite {
+	// Entities with an animation draw its current frame instead.
+	pub texture: Option<TextureHandle>,
+	pub color: [f32; 4],
+}
+
+// Plays the clip of the current animator state from a sprite sheet.
+pub struct Animation {
+	pub sheet: Rc<SpriteSheet>,
+	pub animator: Rc<Animator>,
+	pub state: usize,
+	flags: u32,
+	// Position in the clip, and how long its frame has been shown.
+	position: usize,
+	time: f64,
+	finished: bool,
+}
+
+impl Animation {
+	pub fn new(sheet: Rc<SpriteSheet>, animator: Rc<Animator>) -> Animation {
+		Animation {
+			sheet,
+			state: animator.initial,
+			animator,
+			flags: 0,
+			position: 0,
+			time: 0.,
+			finished: false,
+		}
+	}
+	pub fn set(&mut self, flag: Flag, value: bool) {
+		match value {
+			true => self.flags |= flag.bit(),
+			false => self.flags &= !flag.bit(),
+		}
+	}
+	// Take the first transition of the state whose conditions hold, then move
+	// through the clip. The names of the frame events reached go to on_event.
+	pub fn update(&mut self, dt: f64, mut on_event: impl FnMut(&str)) {
+		let finished = if self.finished { Flag::Finished.bit() } else { 0 };
+		if let Some(next) = self.animator.next(self.state, self.flags | finished) {
+			self.set_state(next);
+			self.animator.states[self.state].events(0).for_each(&mut on_event);
+		}
+		self.set(Flag::AirJump, false);
+		self.advance(dt, on_event);
+	}
+	// Switch to a state without checking its transitions, its clip starts over.
+	pub fn set_state(&mut self, state: usize) {
+		if state != self.state && state < self.animator.states.len() {
+			self.state = state;
+			self.position = 0;
+			self.time = 0.;
+			self.finished = false;
+		}
+	}
+	// Move through the clip by the time each frame is shown.
+	pub fn advance(&mut self, dt: f64, mut on_event: impl FnMut(&str)) {
+		let state = &self.animator.states[self.state];
+		let clip = self.sheet.clip(&state.clip);
+		self.time += dt;
+		while !self.finished {
+			let duration = self.sheet.frames[clip[self.position%clip.len()]].duration;
+			if duration <= 0. || self.time < duration {
+				break
+			}
+			self.time -= duration;
+			if self.position+1 < clip.len() {
+				self.position += 1;
+			}else if state.looping {
+				self.position = 0;
+			}else {
+				self.finished = true;
+				break
+			}
+			state.events(self.position).for_each(&mut on_event);
+		}
+	}
+	pub fn frame(&self) -> &Frame {
+		let clip = self.sheet.clip(&self.animator.states[self.state].clip);
+		&self.sheet.frames[clip[self.position%clip.len()]]
+	}
+}
+
+// Damage dealt to players touching the entity.
+pub type ContactDamage = i32;
+
+// The components of an entity that its controller moves around.
+pub struct Body<'a> {
+	pub transform: &'a mut Transform,
+	pub velocity: &'a mut Velocity,
+	pub collider: &'a mut Collider,
+	pub health: &'a mut Health,
+}
+
+impl<'a> Body<'a> {
+	// Take the damage and get knocked back, for entities without a controller
+	// that takes damage its own way.
+	pub fn hurt(&mut self, damage: &Damage) {
+		if self.health.damage(damage) {
+			*self.velocity = damage.knockback;
+		}
+	}
+}
+
+// The body of an entity borrowed together with the components that move it,
+// the systems tell kinds of entities apart by which of these they have.
+pub struct Parts<'a> {
+	pub body: Body<'a>,
+	pub player: Option<&'a mut Player>,
+	pub mob: Option<&'a mut Mob>,
+	pub animation: Option<&'a mut Animation>,
+}
+
+// All entities and their components.
+pub struct Scene {
+	generations: Vec<u32>,
+	alive: Vec<bool>,
+	free: Vec<u32>,
+	pub transforms: Storage<Transform>,
+	pub velocities: Storage<Velocity>,
+	pub colliders: Storage<Collider>,
+	pub sprites: Storage<Sprite>,
+	pub animations: Storage<Animation>,
+	// Controllers, an entity has at most one of them.
+	pub players: Storage<Player>,
+	pub mobs: Storage<Mob>,
+	pub contact_damages: Storage<ContactDamage>,
+	pub healths: Storage<Health>,
+	// Name of the map object or prefab the entity was spawned from.
+	pub names: Storage<String>,
+}
+
+impl Scene {
+	pub fn new() -> Scene {
+		Scene {
+			generations: Vec::new(),
+			alive: Vec::new(),
+			free: Vec::new(),
+			transforms: Storage::new(),
+			velocities: Storage::new(),
+			colliders: Storage::new(),
+			sprites: Storage::new(),
+			animations: Storage::new(),
+			players: Storage::new(),
+			mobs: Storage::new(),
+			contact_damages: Storage::new(),
+			healths: Storage::new(),
+			names: Storage::new(),
+		}
+	}
+	// Makes a new entity without components, they are inserted into the storages.
+	pub fn spawn(&mut self) -> Entity {
+		match self.free.pop() {
+			Some(index) => {
+				self.alive[index as usize] = true;
+				Entity { index, generation: self.generations[index as usize] }
+			},
+			None => {
+				self.generations.push(0);
+				self.alive.push(true);
+				Entity { index: self.generations.len() as u32-1, generation: 0 }
+			},
+		}
+	}
+	pub fn despawn(&mut self, entity: Entity) {
+		if !self.is_alive(entity) {
+			return
+		}
+		self.transforms.remove(entity);
+		self.velocities.remove(entity);
+		self.colliders.remove(entity);
+		self.sprites.remove(entity);
+		self.animations.remove(entity);
+		self.players.remove(entity);
+		self.mobs.remove(entity);
+		self.contact_damages.remove(entity);
+		self.healths.remove(entity);
+		self.names.remove(entity);
+		let index = entity.index as usize;
+		self.alive[index] = false;
+		self.generations[index] += 1;
+		self.free.push(entity.index);
+	}
+	pub fn is_alive(&self, entity: Entity) -> bool {
+		self.alive.get(entity.index as usize) == Some(&true) && self.generations[entity.index as usize] == entity.generation
+	}
+	pub fn players(&self) -> Vec<Entity> {
+		self.players.iter().map(|(e, _)| e).collect()
+	}
+	pub fn mobs(&self) -> Vec<Entity> {
+		self.mobs.iter().map(|(e, _)| e).collect()
+	}
+	// Borrows the body of an entity together with its controllers and animation.
+	pub fn parts(&mut self, entity: Entity) -> Option<Parts<'_>> {
+		let body = Body {
+			transform: self.transforms.get_mut(entity)?,
+			velocity: self.velocities.get_mut(entity)?,
+			collider: self.colliders.get_mut(entity)?,
+			health: self.healths.get_mut(entity)?,
+		};
+		Some(Parts {
+			body,
+			player: self.players.get_mut(entity),
+			mob: self.mobs.get_mut(entity),
+			animation: self.animations.get_mut(entity),
+		})
+	}
+}
@@ -0,0 +1,68 @@
+use crate::vector::Vec2;
+use crate::METER_IN_PIXELS;
+
+const WATER_COLOR: [f32; 4] = [0.6, 0.8, 1.0, 0.8];
+const SPARK_COLOR: [f32; 4] = [1.0, 0.9, 0.4, 1.0];
+
+struct Particle {
+	pos: Vec2,
+	velocity: Vec2,
+	life: f64,
+	size: f64,
+	color: [f32; 4],
+}
+
+// Short lived particles like splashes, they don't collide with anything.
+pub struct Effects {
+	particles: Vec<Particle>,
+}
+
+impl Effects {
+	pub fn new() -> Effects {
+		Effects {
+			particles: Vec::new(),
+		}
+	}
+	// Spray droplets up from the surface. Spread is fixed so replays look the same.
+	pub fn splash(&mut self, pos: Vec2, strength: f64) {
+		for i in 0..8 {
+			let spread = (i as f64-3.5)/3.5;
+			self.particles.push(Particle {
+				pos,
+				velocity: Vec2::new(spread*1.5*strength, -(2.5-spread.abs())*strength),
+				life: 0.4+0.05*(i%3) as f64,
+				size: 4.,
+				color: WATER_COLOR,
+			});
+		}
+	}
+	// Sparks flying in every direction, where a projectile hit something.
+	pub fn sparks(&mut self, pos: Vec2) {
+		for i in 0..6 {
+			let angle = i as f64*std::f64::consts::PI/3.;
+			self.particles.push(Particle {
+				pos,
+				velocity: Vec2::new(angle.cos()*2., angle.sin()*2.-1.),
+				life: 0.2,
+				size: 3.,
+				color: SPARK_COLOR,
+			});
+		}
+	}
+	pub fn update(&mut self, world: &crate::World, dt: f64) {
+		for particle in &mut self.particles {
+			particle.velocity.y += world.grav_const*dt;
+			particle.pos.x += particle.velocity.x*METER_IN_PIXELS*dt;
+			particle.pos.y += particle.velocity.y*METER_IN_PIXELS*dt;
+			particle.life -= dt;
+		}
+		self.particles.retain(|p| p.life > 0.);
+	}
+	pub fn render(&self, camera: &crate::Camera, renderer: &mut dyn crate::render::Renderer) {
+		let transform = crate::render::view(camera);
+		for p in &self.particles {
+			let (x, y, size) = (p.pos.x, p.pos.y, p.size);
+			renderer.rectangle(p.color, [x-size/2., y-size/2., size, size], transform);
+		}
+	}
+}
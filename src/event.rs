@@ -0,0 +1,21 @@
+use crate::ecs::Entity;
+use crate::vector::Vec2;
+use crate::health::Damage;
+use crate::projectile::{Shot, Target};
+
+// Things that happened during an update, handled by the App after everything
+// has been updated.
+pub enum GameEvent {
+	// A player entered or left a liquid.
+	Splash { pos: Vec2, entered: bool },
+	// A player ran out of health.
+	Died,
+	// A player or a mob fired a projectile.
+	Shoot(Shot),
+	// A projectile hit something, the damage is dealt when the event is handled.
+	Hit { pos: Vec2, target: Target, damage: Damage },
+	// A player is touching a mob, sent every update while they overlap.
+	Touch { player: Entity, mob: Entity },
+	// The animation of an entity reached a frame with an event.
+	Animation { entity: Entity, name: String },
+}
@@ -0,0 +1,259 @@
+use std::fs::File;
+use std::io::{self, Read, Write, BufReader, BufWriter};
+use std::rc::Rc;
+
+use crate::collision::Rect;
+use crate::loader::MapObject;
+use crate::ecs::{Animation, Transform};
+use crate::animator::Animator;
+use crate::sheet::SpriteSheet;
+use crate::vector::Vec2;
+use crate::replay::{read_u8, read_u16, read_u32, read_u64};
+
+const MAGIC: &[u8; 4] = b"PTGG";
+// Changed whenever the file changes, 3 stored animator states by name.
+const VERSION: u8 = 3;
+const GHOST_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.4];
+pub const LATEST_PATH: &str = "replays/latest.ghost";
+
+// Where the player was and what it looked like during a single tick.
+#[derive(Clone, Copy)]
+pub struct Sample {
+	pub x: f32,
+	pub y: f32,
+	pub look_dir: i8,
+	// Animator state of the player, as an index into the state names of the trace.
+	pub state: u8,
+}
+
+// The position trace of a run and the tick each checkpoint was reached on.
+pub struct Trace {
+	pub dt: f64,
+	pub samples: Vec<Sample>,
+	pub splits: Vec<u32>,
+	// Names of the animator states the samples were in. The animator numbers its
+	// states by name, so the numbers change when states are added or removed.
+	pub states: Vec<String>,
+}
+
+impl Trace {
+	pub fn new(dt: f64) -> Trace {
+		Trace {
+			dt,
+			samples: Vec::new(),
+			splits: Vec::new(),
+			states: Vec::new(),
+		}
+	}
+	fn record(&mut self, transform: &Transform, animation: &Animation) {
+		let name = &animation.animator.states[animation.state].name;
+		let state = match self.states.iter().position(|s| s == name) {
+			Some(state) => state,
+			None => {
+				self.states.push(name.clone());
+				self.states.len()-1
+			},
+		};
+		self.samples.push(Sample {
+			x: transform.pos.x as f32,
+			y: transform.pos.y as f32,
+			look_dir: if transform.facing < 0. { -1 } else { 1 },
+			state: state.min(u8::MAX as usize) as u8,
+		});
+	}
+	pub fn save(&self, path: &str) -> io::Result<()> {
+		if let Some(dir) = std::path::Path::new(path).parent() {
+			std::fs::create_dir_all(dir)?;
+		}
+		let mut file = BufWriter::new(File::create(path)?);
+		file.write_all(MAGIC)?;
+		file.write_all(&[VERSION])?;
+		file.write_all(&self.dt.to_bits().to_le_bytes())?;
+		file.write_all(&(self.splits.len() as u32).to_le_bytes())?;
+		for split in &self.splits {
+			file.write_all(&split.to_le_bytes())?;
+		}
+		file.write_all(&[self.states.len() as u8])?;
+		for name in &self.states {
+			file.write_all(&(name.len() as u16).to_le_bytes())?;
+			file.write_all(name.as_bytes())?;
+		}
+		file.write_all(&(self.samples.len() as u32).to_le_bytes())?;
+		for sample in &self.samples {
+			file.write_all(&sample.x.to_le_bytes())?;
+			file.write_all(&sample.y.to_le_bytes())?;
+			file.write_all(&[sample.look_dir as u8, sample.state])?;
+		}
+		file.flush()
+	}
+	pub fn load(path: &str) -> io::Result<Trace> {
+		let mut file = BufReader::new(File::open(path)?);
+		let mut magic = [0u8; 4];
+		file.read_exact(&mut magic)?;
+		if &magic != MAGIC || read_u8(&mut file)? != VERSION {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{:?} is not a valid ghost file!", path)));
+		}
+		let dt = f64::from_bits(read_u64(&mut file)?);
+		let mut splits = Vec::new();
+		for _ in 0..read_u32(&mut file)? {
+			splits.push(read_u32(&mut file)?);
+		}
+		let mut states = Vec::new();
+		for _ in 0..read_u8(&mut file)? {
+			let mut name = vec![0u8; read_u16(&mut file)? as usize];
+			file.read_exact(&mut name)?;
+			states.push(String::from_utf8(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?);
+		}
+		let mut samples = Vec::new();
+		for _ in 0..read_u32(&mut file)? {
+			let x = f32::from_bits(read_u32(&mut file)?);
+			let y = f32::from_bits(read_u32(&mut file)?);
+			let look_dir = read_u8(&mut file)? as i8;
+			let state = read_u8(&mut file)?;
+			samples.push(Sample { x, y, look_dir, state });
+		}
+		Ok(Trace {
+			dt,
+			samples,
+			splits,
+			states,
+		})
+	}
+}
+
+// A translucent player following a previously recorded trace.
+pub struct Ghost {
+	trace: Trace,
+	transform: Transform,
+	animation: Animation,
+	// The animator state of each state name in the trace.
+	states: Vec<usize>,
+}
+
+impl Ghost {
+	fn follow(&mut self, tick: u32) {
+		let sample = match self.trace.samples.get(tick as usize) {
+			Some(sample) => *sample,
+			None => match self.trace.samples.last() {
+				Some(sample) => *sample,
+				None => return,
+			},
+		};
+		self.transform.pos.x = sample.x as f64;
+		self.transform.pos.y = sample.y as f64;
+		self.transform.facing = sample.look_dir as f64;
+		let state = self.states.get(sample.state as usize).cloned().unwrap_or(self.animation.animator.initial);
+		self.animation.set_state(state);
+		self.animation.advance(self.trace.dt, |_| ());
+	}
+}
+
+// Keeps track of the checkpoints of the current map, the trace of the current
+// run and the ghosts the player is racing against.
+pub struct Race {
+	checkpoints: Vec<(i32, Rect)>,
+	best_path: String,
+	best_splits: Vec<u32>,
+	run: Trace,
+	tick: u32,
+	ghosts: Vec<Ghost>,
+}
+
+impl Race {
+	pub fn new() -> Race {
+		Race {
+			checkpoints: Vec::new(),
+			best_path: String::new(),
+			best_splits: Vec::new(),
+			run: Trace::new(0.),
+			tick: 0,
+			ghosts: Vec::new(),
+		}
+	}
+	// Checkpoints are reached in the order given by their "order" property.
+	pub fn add_checkpoint(&mut self, obj: &MapObject) {
+		let order = obj.properties.get("order").and_then(|o| o.parse().ok()).unwrap_or(self.checkpoints.len() as i32);
+		let index = self.checkpoints.iter().position(|(o, _)| *o > order).unwrap_or(self.checkpoints.len());
+		self.checkpoints.insert(index, (order, Rect::new(obj.pos, obj.size)));
+	}
+	// Starts a new run and loads the best run on the map, if there is one.
+	pub fn start(&mut self, map_path: &str, dt: f64) {
+		let name = std::path::Path::new(map_path).file_stem().and_then(|n| n.to_str()).unwrap_or("map");
+		self.best_path = format!("replays/{}.best.ghost", name);
+		self.best_splits = match Trace::load(&self.best_path) {
+			Ok(best) => best.splits,
+			Err(_) => Vec::new(),
+		};
+		self.run = Trace::new(dt);
+		self.tick = 0;
+		self.ghosts.clear();
+	}
+	pub fn best_path(&self) -> &str {
+		&self.best_path
+	}
+	pub fn add_ghost(&mut self, path: &str, sheet: &Rc<SpriteSheet>, animator: &Rc<Animator>) -> io::Result<()> {
+		let trace = Trace::load(path)?;
+		// States the animator doesn't have anymore are shown as its initial state.
+		let states = trace.states.iter().map(|name| {
+			animator.states.iter().position(|s| &s.name == name).unwrap_or_else(|| {
+				println!("Ghost {:?} uses unknown animation state {:?}", path, name);
+				animator.initial
+			})
+		}).collect();
+		let mut ghost = Ghost {
+			trace,
+			transform: Transform::new(Vec2::new(0., 0.), Vec2::new(32., 32.)),
+			animation: Animation::new(sheet.clone(), animator.clone()),
+			states,
+		};
+		ghost.follow(0);
+		self.ghosts.push(ghost);
+		Ok(())
+	}
+	// Must be called once per tick, after the player is updated.
+	pub fn update(&mut self, transform: &Transform, animation: &Animation) {
+		self.run.record(transform, animation);
+		self.tick += 1;
+		for ghost in &mut self.ghosts {
+			ghost.follow(self.tick);
+		}
+
+		let split = self.run.splits.len();
+		let reached = match self.checkpoints.get(split) {
+			Some((_, checkpoint)) => checkpoint.overlaps(&transform.pos, &transform.size),
+			None => false,
+		};
+		if !reached {
+			return
+		}
+		self.run.splits.push(self.tick);
+		let time = self.tick as f64*self.run.dt;
+		print!("\nCheckpoint {}/{}: {:.2}s", split+1, self.checkpoints.len(), time);
+		if let Some(best) = self.best_splits.get(split) {
+			print!(" ({:+.2}s)", time-*best as f64*self.run.dt);
+		}
+		println!();
+
+		if self.run.splits.len() == self.checkpoints.len() {
+			let is_best = match self.best_splits.last() {
+				Some(best) => self.tick < *best,
+				None => true,
+			};
+			if is_best {
+				println!("New best time!");
+				match self.run.save(&self.best_path) {
+					Ok(()) => self.best_splits = self.run.splits.clone(),
+					Err(e) => println!("Could not save best run: {}", e),
+				}
+			}
+		}
+	}
+	pub fn save_run(&self, path: &str) -> io::Result<()> {
+		self.run.save(path)
+	}
+	pub fn render(&self, batch: &mut crate::render::Batch) {
+		for ghost in &self.ghosts {
+			crate::render::draw_frame(batch, &ghost.animation.sheet, ghost.animation.frame(), GHOST_COLOR, &ghost.transform);
+		}
+	}
+}
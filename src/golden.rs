@@ -0,0 +1,120 @@
+// Golden image tests. A small map is drawn with the software renderer and each
+// frame compared to a reference image next to the map. Run them with
+// UPDATE_GOLDEN=1 to write the references again after changing how something
+// looks on purpose.
+use crate::software::Software;
+use crate::vector::Vec2;
+use crate::ability::Abilities;
+use crate::{App, replay, save};
+
+const DIR: &str = "tests/golden";
+const MAP: &str = "tests/golden/room.tmx";
+const SIZE: [u32; 2] = [400, 300];
+// How far a channel of a pixel can be from the reference, and the share of the
+// pixels that can be further off before the frame differs.
+const CHANNEL_TOLERANCE: i32 = 8;
+const PIXEL_TOLERANCE: f64 = 0.002;
+
+fn load() -> App {
+	let replay = replay::Mode::Record(replay::Recorder::new("", MAP, 1./60., &Abilities::default()));
+	let mut app = App::new(MAP, save::SaveData::default(), replay);
+	app.load_level(MAP);
+	app.camera.w = SIZE[0] as f64;
+	app.camera.h = SIZE[1] as f64;
+	app
+}
+
+fn look_at(app: &mut App, pos: Vec2, zoom: f64) {
+	app.camera.zoom = zoom;
+	app.camera.center_on(pos);
+}
+
+// Put the player on the floor of the room, at the tile given by x.
+fn place_player(app: &mut App, x: f64, facing: f64) {
+	let player = app.scene.players()[0];
+	let transform = app.scene.transforms.get_mut(player).unwrap();
+	let floor = 9.*crate::METER_IN_PIXELS-crate::METER_IN_PIXELS/2.;
+	transform.pos = Vec2::new(x*crate::METER_IN_PIXELS, floor-transform.size.y/2.);
+	transform.facing = facing;
+}
+
+fn check(name: &str, app: &mut App) {
+	let mut renderer = Software::new(SIZE[0], SIZE[1]);
+	app.render(&mut renderer);
+	let path = format!("{}/{}.png", DIR, name);
+	if std::env::var_os("UPDATE_GOLDEN").is_some() {
+		renderer.save(&path).unwrap();
+		return
+	}
+	let expected = image::open(&path)
+		.unwrap_or_else(|e| panic!("Could not load {:?}: {}, run the tests with UPDATE_GOLDEN=1 to write it", path, e))
+		.to_rgba();
+	assert_eq!(expected.dimensions(), renderer.image.dimensions(), "{:?} has another size than the frame", path);
+	let differing = expected.pixels().zip(renderer.image.pixels())
+		.filter(|(e, a)| e.0.iter().zip(a.0.iter()).any(|(e, a)| (*e as i32-*a as i32).abs() > CHANNEL_TOLERANCE))
+		.count();
+	if differing as f64 > (SIZE[0]*SIZE[1]) as f64*PIXEL_TOLERANCE {
+		let actual = format!("target/golden/{}.png", name);
+		let saved = std::fs::create_dir_all("target/golden").map_err(|e| e.to_string()).and_then(|_| renderer.save(&actual));
+		panic!("{} pixels differ from {:?}, the frame {}", differing, path, match saved {
+			Ok(()) => format!("was saved to {:?}", actual),
+			Err(e) => format!("could not be saved: {}", e),
+		});
+	}
+}
+
+#[test]
+fn room() {
+	let mut app = load();
+	place_player(&mut app, 2., 1.);
+	look_at(&mut app, Vec2::new(250., 300.), 1.);
+	check("room", &mut app);
+}
+
+// The top right corner of the map, far from where the camera starts.
+#[test]
+fn camera_offset() {
+	let mut app = load();
+	look_at(&mut app, Vec2::new(600., 100.), 1.);
+	check("camera_offset", &mut app);
+}
+
+// Tiles at the edges of a zoomed view have to be drawn even though they
+// aren't in the view without zoom.
+#[test]
+fn zoomed_in() {
+	let mut app = load();
+	look_at(&mut app, Vec2::new(450., 250.), 2.);
+	check("zoomed_in", &mut app);
+}
+
+#[test]
+fn zoomed_out() {
+	let mut app = load();
+	place_player(&mut app, 2., 1.);
+	look_at(&mut app, Vec2::new(375., 225.), 0.5);
+	check("zoomed_out", &mut app);
+}
+
+// The second frame of walking, mirrored.
+#[test]
+fn player_facing_left() {
+	let mut app = load();
+	place_player(&mut app, 10., -1.);
+	let player = app.scene.players()[0];
+	let animation = app.scene.animations.get_mut(player).unwrap();
+	let walk = animation.animator.states.iter().position(|s| s.name == "walk").unwrap();
+	animation.set_state(walk);
+	animation.advance(0.25, |_| ());
+	look_at(&mut app, Vec2::new(475., 375.), 2.);
+	check("player_facing_left", &mut app);
+}
+
+#[test]
+fn results_screen() {
+	let mut app = load();
+	place_player(&mut app, 2., 1.);
+	look_at(&mut app, Vec2::new(250., 300.), 1.);
+	app.level.finished = true;
+	check("results_screen", &mut app);
+}
@@ -0,0 +1,81 @@
+use crate::vector::Vec2;
+
+// How long something can't be hurt again after taking damage, in seconds.
+const INVULNERABLE_TIME: f64 = 1.0;
+// How many times per second something flashes while invulnerable.
+const FLASH_RATE: f64 = 12.;
+// How far below the bottom of the map things die, in pixels.
+const KILL_DEPTH: f64 = 200.;
+
+// A hit from a hazard, a mob or a fall.
+pub struct Damage {
+	pub amount: i32,
+	// Velocity given to whatever was hit, in meters per second.
+	pub knockback: Vec2,
+}
+
+impl Damage {
+	// Knock the target up and away from the position the damage came from.
+	pub fn from(amount: i32, source: &Vec2, target: &Vec2, strength: f64) -> Damage {
+		let away = if target.x < source.x { -1. } else { 1. };
+		Damage {
+			amount,
+			knockback: Vec2::new(away*strength, -strength*0.8),
+		}
+	}
+	// Damage that kills no matter how much health is left.
+	pub fn lethal() -> Damage {
+		Damage {
+			amount: i32::MAX,
+			knockback: Vec2::new(0., 0.),
+		}
+	}
+}
+
+pub struct Health {
+	pub current: i32,
+	pub max: i32,
+	invulnerable_timer: f64,
+}
+
+impl Health {
+	pub fn new(max: i32) -> Health {
+		Health {
+			current: max,
+			max,
+			invulnerable_timer: 0.,
+		}
+	}
+	pub fn update(&mut self, dt: f64) {
+		self.invulnerable_timer -= dt;
+	}
+	// Takes the damage unless still invulnerable from the last hit, returns
+	// true if it was taken.
+	pub fn damage(&mut self, damage: &Damage) -> bool {
+		if self.invulnerable() && damage.amount < i32::MAX || self.dead() {
+			return false
+		}
+		self.current = self.current.saturating_sub(damage.amount).max(0);
+		self.invulnerable_timer = INVULNERABLE_TIME;
+		true
+	}
+	pub fn invulnerable(&self) -> bool {
+		self.invulnerable_timer > 0.
+	}
+	pub fn dead(&self) -> bool {
+		self.current <= 0
+	}
+	pub fn restore(&mut self) {
+		self.current = self.max;
+		self.invulnerable_timer = 0.;
+	}
+	// Blink while invulnerable, returns false on the frames where the sprite is hidden.
+	pub fn visible(&self) -> bool {
+		!self.invulnerable() || (self.invulnerable_timer*FLASH_RATE) as i32 % 2 == 0
+	}
+}
+
+// True once something has fallen through the kill plane below the map.
+pub fn out_of_world(pos: &Vec2, world: &crate::World) -> bool {
+	pos.y > world.h+KILL_DEPTH
+}
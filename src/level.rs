@@ -0,0 +1,200 @@
+use crate::ability::Abilities;
+use crate::collision::{Cube, Rect};
+use crate::loader::MapObject;
+use crate::ecs::Transform;
+use crate::health::Health;
+use crate::mob::Player;
+use crate::pickup::{Door, Inventory, Item, Pickup};
+use crate::vector::Vec2;
+use crate::text;
+
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const SHADE_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+
+// Where the player comes back after dying, and what they had at that point.
+struct Respawn {
+	pos: Vec2,
+	inventory: Inventory,
+	abilities: Abilities,
+}
+
+// Progress through the current map. Touching a checkpoint saves the respawn
+// point, the inventory, the abilities and the pickups and doors used so far,
+// touching the exit finishes the level.
+pub struct Level {
+	pub path: String,
+	// Map to continue with after the results screen, from the "next_level" map property.
+	pub next: Option<String>,
+	pub time: f64,
+	pub deaths: u32,
+	pub finished: bool,
+	// Jump has to be released on the results screen before it can be used to continue.
+	continue_ready: bool,
+	// The area of every checkpoint, and whether it was reached yet.
+	checkpoints: Vec<(Rect, bool)>,
+	respawn: Option<Respawn>,
+	exit: Option<Cube>,
+	pickups: Vec<Pickup>,
+	pub inventory: Inventory,
+	// What the player could do when the level started.
+	start_abilities: Abilities,
+}
+
+impl Level {
+	pub fn new(path: &str) -> Level {
+		Level {
+			path: String::from(path),
+			next: None,
+			time: 0.,
+			deaths: 0,
+			finished: false,
+			continue_ready: false,
+			checkpoints: Vec::new(),
+			respawn: None,
+			exit: None,
+			pickups: Vec::new(),
+			inventory: Inventory::default(),
+			start_abilities: Abilities::default(),
+		}
+	}
+	pub fn start_with(&mut self, abilities: &Abilities) {
+		self.start_abilities = abilities.clone();
+	}
+	pub fn add_checkpoint(&mut self, obj: &MapObject) {
+		self.checkpoints.push((Rect::new(obj.pos, obj.size), false));
+	}
+	pub fn set_exit(&mut self, obj: &MapObject, textures: &mut crate::asset::Textures) {
+		let texture = obj.properties.get("texture").map(|t| t.as_str()).unwrap_or("assets/sprites/exit.png");
+		self.exit = Some(Cube::new(obj.size.x, obj.size.y, obj.pos.x, obj.pos.y, texture, textures));
+	}
+	pub fn add_pickup(&mut self, obj: &MapObject, textures: &mut crate::asset::Textures) {
+		if let Some(pickup) = Pickup::from_object(obj, textures) {
+			self.pickups.push(pickup);
+		}
+	}
+	// Returns how many gems are taken, and how many there are.
+	pub fn collected(&self) -> (usize, usize) {
+		let gems = self.pickups.iter().filter(|p| matches!(p.item, Item::Gem));
+		gems.fold((0, 0), |(taken, total), p| (taken+p.taken as usize, total+1))
+	}
+	pub fn update(&mut self, transform: &Transform, player: &mut Player, health: &mut Health, doors: &mut [Door], unlocked: &mut Abilities, dt: f64) {
+		if self.finished {
+			return
+		}
+		self.time += dt;
+
+		for pickup in &mut self.pickups {
+			pickup.update(transform, player, health, &mut self.inventory, unlocked);
+		}
+		for door in doors.iter_mut() {
+			door.update(transform, &mut self.inventory);
+		}
+		// A checkpoint saves once, when it's first reached, and is respawned at.
+		let reached = self.checkpoints.iter_mut().find(|(c, reached)| !reached && c.overlaps(&transform.pos, &transform.size));
+		if let Some((checkpoint, reached)) = reached {
+			*reached = true;
+			self.respawn = Some(Respawn {
+				pos: checkpoint.pos,
+				inventory: self.inventory.clone(),
+				abilities: player.abilities.clone(),
+			});
+			for pickup in &mut self.pickups {
+				pickup.saved = pickup.taken;
+			}
+			for door in doors.iter_mut() {
+				door.saved = door.open;
+			}
+		}
+
+		let exit = match &self.exit {
+			Some(exit) => Rect::new(exit.pos, exit.size),
+			None => return,
+		};
+		if exit.overlaps(&transform.pos, &transform.size) {
+			self.finished = true;
+			let (taken, total) = self.collected();
+			println!("\nLevel complete in {:.2}s, {}/{} collected, {} deaths", self.time, taken, total, self.deaths);
+		}
+	}
+	// Called when the player dies, everything picked up or opened since the last
+	// checkpoint is put back. Returns the position to respawn at and the abilities
+	// to respawn with.
+	pub fn died(&mut self, spawn: Vec2, doors: &mut [Door]) -> (Vec2, Abilities) {
+		self.deaths += 1;
+		for pickup in &mut self.pickups {
+			pickup.taken = pickup.saved;
+		}
+		for door in doors.iter_mut() {
+			door.open = door.saved;
+		}
+		match &self.respawn {
+			Some(respawn) => {
+				self.inventory = respawn.inventory.clone();
+				(respawn.pos, respawn.abilities.clone())
+			},
+			None => {
+				self.inventory = Inventory::default();
+				(spawn, self.start_abilities.clone())
+			},
+		}
+	}
+	// Waits for a new jump press on the results screen, then returns the map to
+	// load next. Without a next level the same map is played again.
+	pub fn continue_with(&mut self, keystate: &crate::Keys) -> Option<String> {
+		if !keystate.space {
+			self.continue_ready = true;
+			return None
+		}
+		if !self.continue_ready {
+			return None
+		}
+		self.continue_ready = false;
+		Some(self.next.clone().unwrap_or(self.path.clone()))
+	}
+	pub fn render(&mut self, camera: &crate::Camera, batch: &mut crate::render::Batch) {
+		if let Some(exit) = &mut self.exit {
+			exit.render(camera, batch);
+		}
+		for pickup in &mut self.pickups {
+			if !pickup.taken {
+				pickup.cube.render(camera, batch);
+			}
+		}
+	}
+	// Health, time and the inventory in the corner of the screen, and the results
+	// once the level is finished.
+	pub fn render_hud(&self, health: Option<&Health>, camera: &crate::Camera, renderer: &mut dyn crate::render::Renderer) {
+		let (taken, total) = self.collected();
+		let health = health.map(|h| format!("HP {}/{}", h.current.max(0), h.max)).unwrap_or_default();
+		let status = format!("{}  GEMS {}/{}  COINS {}  KEYS {}  TIME {}", health, taken, total,
+		                     self.inventory.coins, self.inventory.key_count(), format_time(self.time));
+		let results = [
+			String::from("LEVEL COMPLETE"),
+			format!("TIME {}", format_time(self.time)),
+			format!("GEMS {}/{}", taken, total),
+			format!("COINS {}", self.inventory.coins),
+			format!("DEATHS {}", self.deaths),
+			String::from(if self.next.is_some() { "JUMP TO CONTINUE" } else { "JUMP TO PLAY AGAIN" }),
+		];
+		let (w, h) = (camera.w, camera.h);
+		let transform = graphics::math::identity();
+
+		text::draw(&status, 10., 10., 3., TEXT_COLOR, transform, renderer);
+		if !self.finished {
+			return
+		}
+		renderer.rectangle(SHADE_COLOR, [0., 0., w, h], transform);
+		let pixel = 5.;
+		let mut y = h/2.-results.len() as f64*pixel*4.;
+		for line in &results {
+			text::draw(line, (w-text::width(line, pixel))/2., y, pixel, TEXT_COLOR, transform, renderer);
+			y += pixel*8.;
+		}
+	}
+}
+
+// Minutes, seconds and hundredths, like 1:02.53.
+fn format_time(time: f64) -> String {
+	let hundredths = (time*100.) as u64;
+	format!("{}:{:02}.{:02}", hundredths/6000, hundredths/100%60, hundredths%100)
+}
@@ -1,47 +1,155 @@
 use roxmltree::Document;
+use std::collections::HashMap;
+use crate::vector::Vec2;
+use crate::platform::Platform;
 
-pub fn load_map(path: &str, app: &mut crate::App, mut window: &mut piston_window::PistonWindow) {
+// How far outside of a damaging tile things get hurt, so standing on it is enough.
+const HAZARD_REACH: f64 = 2.;
+// Tilemap layers, solid tiles are drawn over the ones that can be moved through.
+const SCENERY_LAYER: usize = 0;
+const SOLID_LAYER: usize = 1;
+
+// An object from one of the object layers of a map, converted to world coordinates.
+pub struct MapObject {
+	pub name: String,
+	pub kind: String,
+	pub pos: Vec2,
+	pub size: Vec2,
+	pub properties: HashMap<String, String>,
+	// Shape of the object if it isn't a plain rectangle, the points of
+	// polylines and polygons are in world coordinates.
+	pub shape: Shape,
+}
+
+pub enum Shape {
+	Rectangle,
+	Ellipse,
+	Polyline(Vec<Vec2>),
+	Polygon(Vec<Vec2>),
+}
+
+// A tile from a tileset, keyed by its global id in the map.
+pub struct Tile {
+	pub image: Option<String>,
+	pub properties: HashMap<String, String>,
+}
+
+struct Tmx {
+	data: Vec<u32>,
+	tiles: HashMap<u32, Tile>,
+	width: usize,
+	objects: Vec<MapObject>,
+	properties: HashMap<String, String>,
+}
+
+pub fn load_map(path: &str, app: &mut crate::App) {
 	let map = load_tmx(path);
-	let map_width = map[map.len()-1] as usize;
-	use crate::collision::Cube;
+	let map_width = map.width;
+	use crate::collision::{Cube, Rect, Climbable, Liquid, Hazard, Material};
 	let size = crate::METER_IN_PIXELS;
 	app.world.w = (map_width-1) as f64*size;
-	app.world.h = ((map.len()-1)%map_width) as f64;
+	app.world.h = (map.data.len()/map_width-1) as f64*size;
+	app.world.properties = map.properties;
 	// For each element in map create and push an equivalent Cube element to the game world.
-	for i in 0..map.len()-1 {
-		if map[i] != 0 {
+	for i in 0..map.data.len() {
+		if map.data[i] != 0 {
 			let x = ((i%map_width) as f64)*size;
 			let y = ((i/map_width) as f64)*size;
-			let cube = Cube::new(size, size, x, y, "assets/sprites/brick.png", &mut window);
-			app.obj.push(cube);
+			let tile = map.tiles.get(&map.data[i]);
+			let texture = tile.and_then(|t| t.image.as_ref()).map(|i| i.as_str()).unwrap_or("assets/sprites/brick.png");
+			let mut cube = Cube::new(size, size, x, y, texture, &mut app.textures);
+			if let Some(tile) = tile {
+				cube.material = Material::from_properties(&tile.properties);
+			}
+			// Climbable and liquid tiles can be moved through, everything else is solid.
+			let properties = tile.map(|t| &t.properties);
+			if let Some(kind) = properties.and_then(|p| p.get("climbable")) {
+				let area = Rect::new(cube.pos, cube.size);
+				app.world.climbables.push(Climbable { area, rope: kind == "rope" });
+				app.tilemap.set_tile(SCENERY_LAYER, cube);
+			}else if let Some(properties) = properties.filter(|p| p.contains_key("liquid")) {
+				app.world.liquids.push(Liquid::new(Rect::new(cube.pos, cube.size), properties));
+			}else {
+				// Damaging tiles like spikes are solid, and hurt whatever touches them.
+				if let Some(damage) = properties.and_then(|p| p.get("damage")).and_then(|d| d.parse().ok()) {
+					let reach = Vec2::new(cube.size.x+2.*HAZARD_REACH, cube.size.y+2.*HAZARD_REACH);
+					app.world.hazards.push(Hazard { area: Rect::new(cube.pos, reach), damage });
+				}
+				app.tilemap.set_tile(SOLID_LAYER, cube.clone());
+				app.obj.push(cube);
+			}
 		};
 	}
+
+	for obj in map.objects {
+		match obj.kind.as_str() {
+			"checkpoint" => {
+				app.race.add_checkpoint(&obj);
+				app.level.add_checkpoint(&obj);
+			},
+			"exit" => app.level.set_exit(&obj, &mut app.textures),
+			"trigger" => app.scripts.add_trigger(&obj),
+			"collectible" | "coin" | "key" | "powerup" => app.level.add_pickup(&obj, &mut app.textures),
+			"door" => app.world.doors.push(crate::pickup::Door::from_object(&obj, &mut app.textures)),
+			"platform" => match Platform::from_object(&obj, &mut app.textures) {
+				Some(platform) => app.world.platforms.push(platform),
+				None => println!("Platform {:?} in {:?} has no path!", obj.name, path),
+			},
+			"liquid" => app.world.liquids.push(Liquid::new(Rect::new(obj.pos, obj.size), &obj.properties)),
+			"hazard" => {
+				let damage = obj.properties.get("damage").and_then(|d| d.parse().ok()).unwrap_or(1);
+				app.world.hazards.push(Hazard { area: Rect::new(obj.pos, obj.size), damage });
+			},
+			// Mobs without a "prefab" property are slimes.
+			"mob" => { app.prefabs.spawn_object(&obj, "slime", &mut app.scene, &mut app.textures); },
+			"prefab" => match obj.properties.get("prefab") {
+				Some(_) => { app.prefabs.spawn_object(&obj, "", &mut app.scene, &mut app.textures); },
+				None => println!("Prefab object {:?} in {:?} has no prefab!", obj.name, path),
+			},
+			_ => println!("Unknown object type {:?} in {:?}", obj.kind, path),
+		}
+	}
 }
 
 // Load a map file and parse it to generate a vector of files
-fn load_tmx(path: &str) -> Vec<u8> {
+fn load_tmx(path: &str) -> Tmx {
 	let teststr = std::fs::read_to_string(path).unwrap();
 	let doc = Document::parse(&teststr).unwrap();
 
 	let mut map_string: String = String::new();
 	let mut is_map = false;
+	let mut tile_width = crate::METER_IN_PIXELS;
+	let mut objects = Vec::new();
+	let mut properties = HashMap::new();
+	let mut tiles = HashMap::new();
+	let map_dir = std::path::Path::new(path).parent().unwrap_or(std::path::Path::new(""));
 	// Iterate through xml document and check for map data, if not then panic.
 	for node in doc.descendants() {
 		if node.has_tag_name("data") {
 			map_string = String::from(node.first_child().unwrap().text().unwrap());
 		}else if node.has_tag_name("map") {
 			is_map = true;
+			tile_width = node.attribute("tilewidth").and_then(|w| w.parse().ok()).unwrap_or(tile_width);
+			properties = load_properties(&node);
+		}else if node.has_tag_name("object") {
+			objects.push(node);
+		}else if node.has_tag_name("tileset") {
+			let firstgid = node.attribute("firstgid").and_then(|g| g.parse().ok()).unwrap_or(1);
+			match node.attribute("source") {
+				Some(source) => load_tsx(&map_dir.join(source), firstgid, &mut tiles),
+				None => load_tiles(&node, map_dir, firstgid, &mut tiles),
+			}
 		}
 	}
 
 	if !is_map {
 		panic!("{:?} is not a valid map file!", path);
 	}
-	
-	let mut map_vector: Vec<u8> = Vec::new();
+
+	let mut map_vector: Vec<u32> = Vec::new();
 	let mut obj = 0;
 	let (mut map_width, mut comma_count) = (0, 0);
-	// Convert the map data into a vector of u8
+	// Convert the map data into a vector of u32
 	for byte in map_string.bytes() {
 		match byte {
 			10 => {
@@ -58,9 +166,9 @@ fn load_tmx(path: &str) -> Vec<u8> {
 			},
 			_ => {
 				// Crash if encountered byte is not a base10 number.
-				if byte-48 < 0 || byte-48 > 9 { panic!("Byte is not number! {:?}", byte); };
+				if !(48..=57).contains(&byte) { panic!("Byte is not number! {:?}", byte); };
 
-				obj += byte-48;
+				obj = obj*10 + (byte-48) as u32;
 			},
 		}
 	}
@@ -68,7 +176,90 @@ fn load_tmx(path: &str) -> Vec<u8> {
 	// Push one last object because the data part of the tmx file doesn't end with a comma
 	// which means that the last object wont get pushed.
 	map_vector.push(obj);
-	// Append map width to end of map_vector because there is no other way to get it.
-	map_vector.push(map_width);
-	map_vector
-}
\ No newline at end of file
+
+	// Tiled positions objects in tile pixels from the top left corner of the map, while
+	// the game uses the center of each object and METER_IN_PIXELS sized tiles.
+	let scale = crate::METER_IN_PIXELS/tile_width;
+	let objects = objects.iter().map(|node| {
+		let attr = |name: &str| node.attribute(name).and_then(|v| v.parse::<f64>().ok()).unwrap_or(0.);
+		let size = Vec2::new(attr("width")*scale, attr("height")*scale);
+		let pos = Vec2::new(attr("x")*scale+size.x/2.-crate::METER_IN_PIXELS/2.,
+		                    attr("y")*scale+size.y/2.-crate::METER_IN_PIXELS/2.);
+		let points = |shape: &roxmltree::Node| -> Vec<Vec2> {
+			shape.attribute("points").unwrap_or("").split_whitespace().filter_map(|point| {
+				let mut xy = point.split(',').map(|v| v.parse::<f64>());
+				match (xy.next(), xy.next()) {
+					(Some(Ok(x)), Some(Ok(y))) => Some(Vec2::new((attr("x")+x)*scale-crate::METER_IN_PIXELS/2.,
+					                                             (attr("y")+y)*scale-crate::METER_IN_PIXELS/2.)),
+					_ => None,
+				}
+			}).collect()
+		};
+		let mut shape = Shape::Rectangle;
+		for child in node.children() {
+			if child.has_tag_name("ellipse") {
+				shape = Shape::Ellipse;
+			}else if child.has_tag_name("polyline") {
+				shape = Shape::Polyline(points(&child));
+			}else if child.has_tag_name("polygon") {
+				shape = Shape::Polygon(points(&child));
+			}
+		}
+		MapObject {
+			name: String::from(node.attribute("name").unwrap_or("")),
+			kind: String::from(node.attribute("type").unwrap_or("")),
+			pos,
+			size,
+			properties: load_properties(node),
+			shape,
+		}
+	}).collect();
+
+	Tmx {
+		data: map_vector,
+		width: map_width as usize,
+		objects,
+		properties,
+		tiles,
+	}
+}
+
+// Load an external tileset file.
+fn load_tsx(path: &std::path::Path, firstgid: u32, tiles: &mut HashMap<u32, Tile>) {
+	let text = std::fs::read_to_string(path).unwrap();
+	let doc = Document::parse(&text).unwrap();
+	let dir = path.parent().unwrap_or(std::path::Path::new(""));
+	load_tiles(&doc.root_element(), dir, firstgid, tiles);
+}
+
+// Collect the tiles of a tileset node. Image paths in a tileset are relative to the
+// file the tileset is in.
+fn load_tiles(tileset: &roxmltree::Node, dir: &std::path::Path, firstgid: u32, tiles: &mut HashMap<u32, Tile>) {
+	for tile in tileset.children().filter(|t| t.has_tag_name("tile")) {
+		let id: u32 = match tile.attribute("id").and_then(|i| i.parse().ok()) {
+			Some(id) => id,
+			None => continue,
+		};
+		let image = tile.children()
+			.find(|i| i.has_tag_name("image"))
+			.and_then(|i| i.attribute("source"))
+			.map(|source| dir.join(source).to_string_lossy().into_owned());
+		tiles.insert(firstgid+id, Tile {
+			image,
+			properties: load_properties(&tile),
+		});
+	}
+}
+
+// Collect the <properties> of an xml node into a map of name and value.
+fn load_properties(node: &roxmltree::Node) -> HashMap<String, String> {
+	let mut properties = HashMap::new();
+	for child in node.children().filter(|c| c.has_tag_name("properties")) {
+		for property in child.children().filter(|p| p.has_tag_name("property")) {
+			if let (Some(name), Some(value)) = (property.attribute("name"), property.attribute("value")) {
+				properties.insert(String::from(name), String::from(value));
+			}
+		}
+	}
+	properties
+}
@@ -12,23 +12,56 @@ mod world;
 mod render;
 mod mob;
 mod loader;
+mod replay;
+mod ghost;
+mod movement;
+mod ability;
+mod save;
+mod event;
+mod effect;
+mod platform;
+mod physics;
+mod ai;
+mod health;
+mod level;
+mod text;
+mod pickup;
+mod projectile;
+mod ecs;
+mod system;
+mod prefab;
+mod script;
+mod asset;
+mod sheet;
+mod animator;
+mod benchmark;
+mod tilemap;
+mod software;
+#[cfg(test)]
+mod golden;
 
-use collision::Cube;
 
 use piston::window::WindowSettings;
 use piston::event_loop::*;
 use piston::input::*;
 use piston_window::PistonWindow;
-use opengl_graphics::{ GlGraphics, OpenGL };
+use opengl_graphics::OpenGL;
+use std::rc::Rc;
 
 const METER_IN_PIXELS: f64 = 50.0;
+const PLAYER_PREFAB: &str = "player";
+const WINDOW_SIZE: [u32; 2] = [800, 600];
 
+#[derive(Clone, Copy, PartialEq)]
 pub struct Keys {
     space: bool,
     a: bool,
     d: bool,
     s: bool,
     w: bool,
+    shift: bool,
+    fire: bool,
+    pound: bool,
 }
 
 impl Keys {
@@ -39,6 +72,27 @@ impl Keys {
             d: false,
             s: false,
             w: false,
+            shift: false,
+            fire: false,
+            pound: false,
+        }
+    }
+    // Packs the key state into a single byte, used by the replay files.
+    fn to_bits(self) -> u8 {
+        (self.space as u8) | (self.a as u8) << 1 | (self.d as u8) << 2 |
+        (self.s as u8) << 3 | (self.w as u8) << 4 | (self.shift as u8) << 5 |
+        (self.fire as u8) << 6 | (self.pound as u8) << 7
+    }
+    fn from_bits(bits: u8) -> Keys {
+        Keys {
+            space: bits & 1 != 0,
+            a: bits & 1 << 1 != 0,
+            d: bits & 1 << 2 != 0,
+            s: bits & 1 << 3 != 0,
+            w: bits & 1 << 4 != 0,
+            shift: bits & 1 << 5 != 0,
+            fire: bits & 1 << 6 != 0,
+            pound: bits & 1 << 7 != 0,
         }
     }
 }
@@ -47,29 +101,106 @@ pub struct World {
     grav_const: f64,
     w: f64,
     h: f64,
+    properties: std::collections::HashMap<String, String>,
+    climbables: Vec<collision::Climbable>,
+    liquids: Vec<collision::Liquid>,
+    hazards: Vec<collision::Hazard>,
+    platforms: Vec<platform::Platform>,
+    doors: Vec<pickup::Door>,
+    spawn: vector::Vec2,
+}
+
+impl World {
+    fn new() -> World {
+        World {
+            grav_const: 9.807,
+            w: 0.,
+            h: 0.,
+            properties: std::collections::HashMap::new(),
+            climbables: Vec::new(),
+            liquids: Vec::new(),
+            hazards: Vec::new(),
+            platforms: Vec::new(),
+            doors: Vec::new(),
+            spawn: vector::Vec2::new(0., 0.),
+        }
+    }
 }
 
 pub struct Camera {
     position: vector::Vec2,
+    // Point to look at instead of following the player, set by scripts.
+    focus: Option<vector::Vec2>,
     zoom: f64,
     w: f64,
     h: f64,
 }
 
+impl Camera {
+    fn center_on(&mut self, pos: vector::Vec2) {
+        self.position.x = (pos.x-self.w)*self.zoom+(self.w*self.zoom)/2.;
+        self.position.y = (pos.y-self.h)*self.zoom+(self.h*self.zoom)/2.;
+    }
+}
+
 pub struct App {
-    gl: GlGraphics, // OpenGL drawing backend.
-    mobs: Vec<collision::Cube>,
+    scene: ecs::Scene,
+    textures: asset::Textures,
+    batch: render::Batch,
+    prefabs: prefab::Prefabs,
+    scripts: script::Scripts,
     obj: Vec<collision::Cube>,
-    players: Vec<mob::Player>,
+    tilemap: tilemap::Tilemap,
     keystate: Keys,
     world: World,
     camera: Camera,
+    replay: replay::Mode,
+    race: ghost::Race,
+    reload_timer: f64,
+    events: Vec<event::GameEvent>,
+    effects: effect::Effects,
+    projectiles: projectile::Projectiles,
+    level: level::Level,
+    // Power-ups collected while playing are unlocked in the save too.
+    save_data: save::SaveData,
+    // Map to switch to after this update, set when leaving the results screen.
+    pending_level: Option<String>,
 }
 
 impl App {
-    fn render(&mut self, args: &RenderArgs, e: &Event, window: &mut PistonWindow) {
-        use graphics::*;
-
+    // A game with the player spawned, without a map until one is loaded.
+    fn new(map_path: &str, save_data: save::SaveData, replay: replay::Mode) -> App {
+        let mut app = App {
+            scene: ecs::Scene::new(),
+            textures: asset::Textures::new(),
+            batch: render::Batch::new(),
+            prefabs: prefab::Prefabs::new(prefab::PREFAB_DIR),
+            scripts: script::Scripts::new(),
+            obj: Vec::new(),
+            tilemap: tilemap::Tilemap::new(),
+            keystate: Keys::new(),
+            world: World::new(),
+            camera: Camera {
+                position: vector::Vec2::new(0.0, 0.0),
+                focus: None,
+                zoom: 1.0,
+                w: 0.,
+                h: 0.,
+            },
+            replay,
+            race: ghost::Race::new(),
+            reload_timer: 0.,
+            events: Vec::new(),
+            effects: effect::Effects::new(),
+            projectiles: projectile::Projectiles::new(),
+            level: level::Level::new(map_path),
+            save_data,
+            pending_level: None,
+        };
+        app.prefabs.spawn(PLAYER_PREFAB, &mut app.scene, vector::Vec2::new(0., 0.), &mut app.textures).expect("Could not spawn the player");
+        app
+    }
+    fn render(&mut self, renderer: &mut dyn render::Renderer) {
         #[allow(dead_code)]
         const RED:   [f32; 4] = [1.0, 0.0, 0.0, 1.0];
         #[allow(dead_code)]
@@ -79,36 +210,218 @@ impl App {
         #[allow(dead_code)]
         const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
 
-		self.gl.draw(args.viewport(), |_c, gl| {
-	        clear(GREEN, gl);
-	    });
+        renderer.clear(GREEN);
+
+        // Textures are drawn in batches, the level and ghosts first and the entities on top of the projectiles.
+        renderer.sync(&self.textures);
+        let batch = &mut self.batch;
+        self.tilemap.render(&self.textures, &self.camera, batch);
 
-        for obj in &mut self.obj {
-            //obj._render(&mut self.gl, &self.camera, RED, args);
-            obj.render(&self.camera, window, e);
+        for platform in &mut self.world.platforms {
+            platform.render(&self.camera, batch);
         }
 
-        for mob in &mut self.mobs {
-            //mob._render(&mut self.gl, &self.camera, BLUE, args);
-            mob.render(&self.camera, window, e);
+        for door in &mut self.world.doors {
+            door.render(&self.camera, batch);
         }
 
-        for player in &mut self.players {
-            player.render(&self.camera, window, e);
+        self.level.render(&self.camera, batch);
+        self.race.render(batch);
+        batch.draw(renderer, &self.camera);
+        self.projectiles.render(&self.camera, renderer);
+        system::render(&self.scene, batch);
+        batch.draw(renderer, &self.camera);
+        self.effects.render(&self.camera, renderer);
+
+        // Liquids are drawn on top so whatever is in them looks submerged.
+        for liquid in &self.world.liquids {
+            liquid.render(&self.camera, renderer);
         }
+
+        let player = self.scene.players().first().and_then(|p| self.scene.healths.get(*p));
+        self.level.render_hud(player, &self.camera, renderer);
+        renderer.finish();
     }
 
     fn update(&mut self, args: &UpdateArgs) {
-        for player in &mut self.players {
-            player.update(&mut self.keystate, &self.world, &mut self.camera, &self.obj, args);
+        let mut args = *args;
+        match &mut self.replay {
+            replay::Mode::Record(recorder) => recorder.record(&self.keystate),
+            replay::Mode::Play(playback) => {
+                match playback.next_keys() {
+                    Some(keys) => self.keystate = keys,
+                    None => return,
+                }
+                // Always use the recorded timestep and movement so the simulation stays deterministic.
+                args.dt = playback.header.dt;
+                if let Some(file) = playback.movement() {
+                    for (_, player) in self.scene.players.iter_mut() {
+                        player.replay_movement(file);
+                    }
+                }
+            },
         }
 
-        // This can lag the game quite a bit, or atleast slow down the updates.
-        if self.players.len() > 0 && false { 
-            let _true_velocity = (self.players[0].velocity.y*self.players[0].velocity.y+self.players[0].velocity.x*self.players[0].velocity.x).sqrt();
-            print!("\rVelocity: {:.2} m/s  ", _true_velocity as f32);
-            print!("Animation length: {:.2}  ", self.players[0].ani_length);
-            print!("W/H: {:.2}/{:.2}   ", self.camera.w, self.camera.h);
+        // Everything stands still on the results screen.
+        if self.level.finished {
+            if let Some(path) = self.level.continue_with(&self.keystate) {
+                self.pending_level = Some(path);
+            }
+        }else {
+            self.simulate(&args);
+        }
+
+        match &mut self.replay {
+            replay::Mode::Record(recorder) => recorder.end_tick(&self.scene),
+            replay::Mode::Play(playback) => playback.end_tick(&self.scene),
+        }
+
+        // Look for changed movement profiles, scripts and textures twice a second so they can be tuned live.
+        // This happens between ticks so a recording knows which tick a new movement profile was first used on.
+        // A replay is played back with what it was recorded with.
+        self.reload_timer -= args.dt;
+        if self.reload_timer <= 0. {
+            self.reload_timer = 0.5;
+            if let replay::Mode::Record(recorder) = &mut self.replay {
+                for (_, player) in self.scene.players.iter_mut() {
+                    player.reload_movement();
+                    if let Some(source) = &player.movement_source {
+                        recorder.record_movement(&source.file);
+                    }
+                }
+                self.scripts.reload_changed();
+            }
+            self.textures.reload_changed();
+        }
+    }
+    fn simulate(&mut self, args: &UpdateArgs) {
+        for platform in &mut self.world.platforms {
+            platform.update(args.dt);
+        }
+
+        system::run(&mut self.scene, &mut system::Frame {
+            world: &self.world,
+            collidables: &self.obj,
+            keystate: &mut self.keystate,
+            camera: &mut self.camera,
+            events: &mut self.events,
+            args,
+        });
+        self.scripts.update(&self.scene, &self.keystate, args.dt);
+        self.projectiles.update(&self.world, &self.obj, &self.scene, &mut self.events, args.dt);
+        self.effects.update(&self.world, args.dt);
+        self.handle_events();
+
+        if let Some(&entity) = self.scene.players().first() {
+            if let (Some(transform), Some(animation)) = (self.scene.transforms.get(entity), self.scene.animations.get(entity)) {
+                self.race.update(transform, animation);
+            }
+            if let Some(ecs::Parts { body, player: Some(player), .. }) = self.scene.parts(entity) {
+                self.level.update(body.transform, player, body.health, &mut self.world.doors, &mut self.save_data.abilities, args.dt);
+            }
+        }
+    }
+    // Replace the current map with the one at the path, and put the players at its spawn.
+    fn load_level(&mut self, map_path: &str) {
+        for mob in self.scene.mobs() {
+            self.scene.despawn(mob);
+        }
+        self.projectiles.clear();
+        self.scripts.clear();
+        self.camera.focus = None;
+        self.obj.clear();
+        self.tilemap.clear();
+        self.world = World::new();
+        self.level = level::Level::new(map_path);
+        // The map adds its own checkpoints to race through.
+        self.race = ghost::Race::new();
+        loader::load_map(map_path, self);
+        self.level.next = self.world.properties.get("next_level").cloned();
+        self.world.spawn = vector::Vec2::new(self.world.w/2., 0.);
+
+        for entity in self.scene.players() {
+            if let Some(ecs::Parts { mut body, player: Some(player), .. }) = self.scene.parts(entity) {
+                player.respawn(&mut body, self.world.spawn);
+                if let Some(path) = &player.movement_path {
+                    let source = match &mut self.replay {
+                        replay::Mode::Record(_) => movement::ProfileSource::new(path, &self.world.properties),
+                        replay::Mode::Play(playback) => {
+                            let file = playback.movement().unwrap_or_default();
+                            movement::ProfileSource::recorded(path, file, &self.world.properties)
+                        },
+                    };
+                    player.set_movement(source);
+                    if let (replay::Mode::Record(recorder), Some(source)) = (&mut self.replay, &player.movement_source) {
+                        recorder.record_movement(&source.file);
+                    }
+                }
+                player.abilities = self.save_data.abilities.with_map(&self.world.properties);
+                self.level.start_with(&player.abilities);
+            }
+        }
+        self.scripts.start(&self.world.properties, &self.scene, &self.keystate);
+        println!("Textures: {}", self.textures.stats());
+    }
+    fn handle_events(&mut self) {
+        // Handling an event can cause new ones, like a hit killing the player.
+        while !self.events.is_empty() {
+            let events = std::mem::take(&mut self.events);
+            for event in events {
+                self.handle_event(event);
+            }
+        }
+    }
+    fn handle_event(&mut self, event: event::GameEvent) {
+        match event {
+            event::GameEvent::Splash { pos, entered } => {
+                self.effects.splash(pos, if entered { 1.0 } else { 0.6 });
+            },
+            event::GameEvent::Died => {
+                let (checkpoint, abilities) = self.level.died(self.world.spawn, &mut self.world.doors);
+                for entity in self.scene.players() {
+                    if let Some(ecs::Parts { mut body, player: Some(player), .. }) = self.scene.parts(entity) {
+                        player.abilities = abilities.clone();
+                        player.respawn(&mut body, checkpoint);
+                    }
+                }
+                self.scripts.on_death();
+            },
+            event::GameEvent::Shoot(shot) => self.projectiles.spawn(shot),
+            event::GameEvent::Hit { pos, target, damage } => {
+                self.effects.sparks(pos);
+                if let projectile::Target::Entity(entity) = target {
+                    system::hurt(&mut self.scene, entity, &damage, &mut self.events);
+                    self.scripts.on_hit(entity, damage.amount);
+                }
+            },
+            event::GameEvent::Touch { player, mob } => self.scripts.on_touch(player, mob),
+            event::GameEvent::Animation { entity, name } => self.scripts.on_animation(entity, &name),
+        }
+    }
+    // Carry out what the scripts asked for during the last update.
+    fn run_script_commands(&mut self) {
+        for command in self.scripts.take_commands() {
+            match command {
+                script::Command::Spawn { prefab, pos } => {
+                    self.prefabs.spawn(&prefab, &mut self.scene, pos, &mut self.textures);
+                },
+                script::Command::Despawn(entity) => self.scene.despawn(entity),
+                script::Command::Move { entity, pos } => if let Some(transform) = self.scene.transforms.get_mut(entity) {
+                    transform.pos = pos;
+                },
+                script::Command::Push { entity, velocity } => if let Some(v) = self.scene.velocities.get_mut(entity) {
+                    *v = velocity;
+                },
+                script::Command::Hurt { entity, amount } => {
+                    let damage = health::Damage { amount, knockback: vector::Vec2::new(0., 0.) };
+                    system::hurt(&mut self.scene, entity, &damage, &mut self.events);
+                },
+                script::Command::LookAt(focus) => self.camera.focus = focus,
+                script::Command::Zoom(zoom) => self.camera.zoom = zoom,
+                script::Command::OpenDoor(name) => for door in self.world.doors.iter_mut().filter(|d| d.name == name) {
+                    door.open = true;
+                },
+            }
         }
     }
     fn btn_press(&mut self, key: &piston::Button) {
@@ -117,6 +430,9 @@ impl App {
         if key == &Button::Keyboard(Key::D) { self.keystate.d = true; }
         if key == &Button::Keyboard(Key::S) { self.keystate.s = true; }
         if key == &Button::Keyboard(Key::W) { self.keystate.w = true; }
+        if key == &Button::Keyboard(Key::LShift) { self.keystate.shift = true; }
+        if key == &Button::Keyboard(Key::J) { self.keystate.fire = true; }
+        if key == &Button::Keyboard(Key::K) { self.keystate.pound = true; }
         //println!("\nPressed keyboard key '{:?}'", key);
     }
     fn btn_release(&mut self, key: &piston::Button) {
@@ -125,78 +441,136 @@ impl App {
         if key == &Button::Keyboard(Key::D) { self.keystate.d = false; }
         if key == &Button::Keyboard(Key::S) { self.keystate.s = false; }
         if key == &Button::Keyboard(Key::W) { self.keystate.w = false; }
+        if key == &Button::Keyboard(Key::LShift) { self.keystate.shift = false; }
+        if key == &Button::Keyboard(Key::J) { self.keystate.fire = false; }
+        if key == &Button::Keyboard(Key::K) { self.keystate.pound = false; }
         //println!("\nReleased keyboard key '{:?}'", key);
     }
 }
 
+// Race against the best run on the map and the given ghosts.
+fn start_race(app: &mut App, map_path: &str, ghost_paths: &[String], sheet: &Rc<sheet::SpriteSheet>, animator: &Rc<animator::Animator>, dt: f64) {
+    app.race.start(map_path, dt);
+    let mut paths = Vec::new();
+    let best_path = String::from(app.race.best_path());
+    if std::path::Path::new(&best_path).exists() {
+        paths.push(best_path);
+    }
+    paths.extend_from_slice(ghost_paths);
+    for path in &paths {
+        if let Err(e) = app.race.add_ghost(path, sheet, animator) {
+            println!("Could not load ghost {:?}: {}", path, e);
+        }
+    }
+}
+
 fn main() {
+    let mut map_path = String::from("assets/maps/test.tmx");
+    let mut record_path = String::from(replay::DEFAULT_PATH);
+    let mut replay_path: Option<String> = None;
+    let mut ghost_paths: Vec<String> = Vec::new();
+    let mut benchmark: Option<benchmark::Benchmark> = None;
+    let mut screenshot: Option<String> = None;
+    let mut cli_args = std::env::args().skip(1);
+    while let Some(arg) = cli_args.next() {
+        match arg.as_str() {
+            "--map" => map_path = cli_args.next().expect("--map needs a path"),
+            "--record" => record_path = cli_args.next().expect("--record needs a path"),
+            "--replay" => replay_path = Some(cli_args.next().expect("--replay needs a path")),
+            "--ghost" => ghost_paths.push(cli_args.next().expect("--ghost needs a path")),
+            "--benchmark" => benchmark = Some(benchmark::Benchmark::new(
+                cli_args.next().and_then(|s| s.parse().ok()).expect("--benchmark needs a number of frames"))),
+            "--screenshot" => screenshot = Some(cli_args.next().expect("--screenshot needs a path")),
+            _ => panic!("Unknown argument {:?}", arg),
+        }
+    }
+
+    let mut event_settings = EventSettings::new();
+    let mut save_data = save::SaveData::load(save::SAVE_PATH);
+    let replay_mode = match replay_path {
+        Some(path) => {
+            let playback = replay::Playback::load(&path).unwrap();
+            map_path = playback.header.map_path.clone();
+            event_settings = event_settings.ups((1./playback.header.dt).round() as u64);
+            // Play with the abilities of the recording, the save isn't touched.
+            save_data.abilities = playback.header.abilities.clone();
+            replay::Mode::Play(playback)
+        },
+        None => replay::Mode::Record(replay::Recorder::new(&record_path, &map_path, 1./event_settings.ups as f64, &save_data.abilities)),
+    };
+
+    // Create a new game and run it.
+    let mut app = App::new(&map_path, save_data, replay_mode);
+    // Ghosts look like the player.
+    let (player_sheet, player_animator) = app.scene.players().first().and_then(|p| app.scene.animations.get(*p))
+        .map(|a| (a.sheet.clone(), a.animator.clone()))
+        .expect("The player has no sprite sheet");
+    app.load_level(&map_path);
+
+    // Ghosts given on the command line only race on the first map.
+    let dt = 1./event_settings.ups as f64;
+    start_race(&mut app, &map_path, &ghost_paths, &player_sheet, &player_animator, dt);
+
+    // Render the first frame to an image without opening a window.
+    if let Some(path) = screenshot {
+        app.camera.w = WINDOW_SIZE[0] as f64;
+        app.camera.h = WINDOW_SIZE[1] as f64;
+        app.update(&UpdateArgs { dt });
+        let mut renderer = software::Software::new(WINDOW_SIZE[0], WINDOW_SIZE[1]);
+        app.render(&mut renderer);
+        match renderer.save(&path) {
+            Ok(()) => println!("Saved screenshot to {:?}", path),
+            Err(e) => println!("Could not save screenshot: {}", e),
+        }
+        return
+    }
+
     // Change this to OpenGL::V2_1 if not working.
     let opengl = OpenGL::V3_2;
 
     // Create a window.
     let mut window: PistonWindow = WindowSettings::new(
             "spinning-square",
-            [800, 600]
+            WINDOW_SIZE
         )
         .exit_on_esc(true)
         .graphics_api(opengl)
         .build()
         .unwrap();
+    let mut gpu = render::Gpu::new(&mut window);
 
-    // Create a new game and run it.
-    let mut app = App {
-        gl: GlGraphics::new(opengl),
-        mobs: Vec::new(),
-        obj: Vec::new(),
-        players: Vec::new(),
-        keystate: Keys::new(),
-        world: World {
-            grav_const: 9.807,
-            w: 0.,
-            h: 0.,
-        },
-        camera: Camera {
-            position: vector::Vec2::new(0.0, 0.0),
-            zoom: 1.0,
-            w: 0.,
-            h: 0.,
-        },
-    };
-
-    let cube1 = Cube::new(32., 32., 200., 200., "assets/sprites/brick.png", &mut window);
-    let cube2 = Cube::new(32., 32., 232., 200., "assets/sprites/brick.png", &mut window);
-
-    app.mobs.push(cube1);
-    app.mobs.push(cube2);
-
-    let map = loader::load_map("assets/maps/test.tmx", &mut app, &mut window);
-
-    let mut player_animation: Vec<&str> = Vec::new();
-    let ani_str = "assets/sprites/Player1.png";
-    player_animation.push(ani_str);
-    player_animation.push("assets/sprites/Player2.png");
-    for i in 0..4 {
-        player_animation.push(ani_str);
-    }
-    let player1 = mob::Player::new(32., 32., app.world.w/2., app.world.h/2., &player_animation, &mut window);
-    app.players.push(player1);
-
-    let mut events = Events::new(EventSettings::new());
+    let mut events = Events::new(event_settings);
     while let Some(e) = events.next(&mut window) {
-        if let Some(r) = e.render_args() {
-            app.render(&r, &e, &mut window);
+        if e.render_args().is_some() {
+            let mut renderer = render::Piston::new(&mut window, &e, &mut gpu);
+            match &mut benchmark {
+                Some(benchmark) => if benchmark.render(&mut app, &mut renderer) {
+                    return
+                },
+                None => app.render(&mut renderer),
+            }
         }
 
-        if let Some(u) = e.update_args() {
+        // Nothing moves during a benchmark.
+        if let (Some(u), None) = (e.update_args(), &benchmark) {
             app.update(&u);
+            app.run_script_commands();
         }
 
-        if let Some(key) = e.press_args() {
-            app.btn_press(&key);
+        if let Some(path) = app.pending_level.take() {
+            app.load_level(&path);
+            start_race(&mut app, &path, &[], &player_sheet, &player_animator, dt);
         }
 
-        if let Some(key) = e.release_args() {
-            app.btn_release(&key);
+        // Keyboard input is ignored while a replay is feeding the keys.
+        if let replay::Mode::Record(_) = app.replay {
+            if let Some(key) = e.press_args() {
+                app.btn_press(&key);
+            }
+
+            if let Some(key) = e.release_args() {
+                app.btn_release(&key);
+            }
         }
 
         if let Some(r) = e.resize_args() {
@@ -205,5 +579,17 @@ fn main() {
             app.camera.h = r.draw_size[1] as f64;
         }
     }
+    if let replay::Mode::Record(recorder) = &app.replay {
+        match recorder.save() {
+            Ok(()) => println!("\nSaved replay to {:?}", record_path),
+            Err(e) => println!("\nCould not save replay: {}", e),
+        }
+        if let Err(e) = app.race.save_run(ghost::LATEST_PATH) {
+            println!("Could not save ghost: {}", e);
+        }
+        if let Err(e) = app.save_data.save(save::SAVE_PATH) {
+            println!("Could not save the game: {}", e);
+        }
+    }
     println!("\nDone!");
 }
\ No newline at end of file
@@ -1,169 +1,361 @@
+use std::collections::HashMap;
 use crate::METER_IN_PIXELS;
+use crate::collision::{Cube, Rect, Material, Side};
+use crate::physics;
+use crate::vector::Vec2;
+use crate::movement::{MovementProfile, ProfileSource};
+use crate::ability::Abilities;
+use crate::event::GameEvent;
+use crate::health::Damage;
+use crate::projectile::{self, Shot};
+use crate::animator::Flag;
+use crate::ecs::{Animation, Body};
+use crate::system::Frame;
 
-pub enum Animations {
-	Stand,
-	Walk1,
-	Walk2,
-	Walk3,
-	Jump,
-	None,
-}
-
-impl Animations {
-	// TODO: Change to something like 'is(&self, Animations)'
-	pub fn is_jump(&self) -> bool {
-		match self {
-			Animations::Jump => true,
-			_ => false,
-		}
-	}
-}
+// Share of the vertical speed the air takes away each update.
+const AIR_RESISTANCE: f64 = 1./650.;
+// Friction of a wall with normal grip, per second.
+const WALL_FRICTION: f64 = 60.;
+// How far a player must be pushed into the terrain or a closed door to be crushed.
+const CRUSH_MARGIN: f64 = 4.;
+// Speed things are knocked back with when they get hurt, in meters per second.
+pub const KNOCKBACK: f64 = 4.;
+// The players shots fly straight ahead, in meters per second.
+const SHOT_SPEED: f64 = 10.;
+const SHOT_LIFETIME: f64 = 0.8;
+// Seconds between shots while fire is held.
+const SHOT_COOLDOWN: f64 = 0.3;
+const PLAYER_SHOT_COLOR: [f32; 4] = [1.0, 1.0, 0.3, 1.0];
+const MOB_SHOT_COLOR: [f32; 4] = [1.0, 0.3, 0.2, 1.0];
 
 pub struct MobState {
-	pub look_dir: f64,
 	pub walljump_time: f64,
 	pub walljump: bool,
-	pub air_resistance: f64,
-	pub friction: f64,
+	pub jump_held: bool,
+	pub jumping: bool,
+	pub coyote_timer: f64,
+	pub jump_buffer_timer: f64,
+	pub on_wall: bool,
+	pub dash_held: bool,
+	pub dash_timer: f64,
+	pub dash_cooldown: f64,
+	pub dash_dir: Vec2,
+	pub invulnerable_timer: f64,
+	pub air_jumps_left: u32,
+	pub ground_pound_held: bool,
+	pub ground_pounding: bool,
+	pub climbing: bool,
+	pub climb_cooldown: f64,
+	pub in_liquid: bool,
+	pub breath: f64,
+	// Highest point since last standing on something, to measure falls.
+	pub fall_top: f64,
+	pub fire_cooldown: f64,
+	pub ground: Material,
+	pub riding: Option<usize>,
 }
 
 impl MobState {
 	pub fn new() -> MobState {
 		MobState {
-			look_dir: 1.0,
 			walljump_time: 0.0,
 			walljump: false,
-			air_resistance: 1./650.,
-			friction: 60.,
+			jump_held: false,
+			jumping: false,
+			coyote_timer: 0.0,
+			jump_buffer_timer: 0.0,
+			on_wall: false,
+			dash_held: false,
+			dash_timer: 0.0,
+			dash_cooldown: 0.0,
+			dash_dir: Vec2::new(0., 0.),
+			invulnerable_timer: 0.0,
+			air_jumps_left: 0,
+			ground_pound_held: false,
+			ground_pounding: false,
+			climbing: false,
+			climb_cooldown: 0.0,
+			in_liquid: false,
+			breath: 0.0,
+			fall_top: 0.0,
+			fire_cooldown: 0.0,
+			ground: Material::new(),
+			riding: None,
 		}
 	}
 }
 
-use crate::vector::Vec2;
 pub struct Player {
-	pub size: Vec2,
-	pub pos: Vec2,
-	pub velocity: Vec2,
-	pub on_ground: bool,
-	pub rotation: f64,
-	pub color: [f64; 4],
-	pub texture: crate::mob::Animations,
-	pub animation: crate::render::PlayerAnimation,
-	pub ani_length: f64,
 	pub state: crate::mob::MobState,
+	pub movement: MovementProfile,
+	pub movement_source: Option<ProfileSource>,
+	pub abilities: Abilities,
+	// Path of the movement profile, loaded with the overrides of each map.
+	pub movement_path: Option<String>,
 }
 
 impl Player {
-	pub fn new(sx: f64, sy: f64, px: f64, py: f64, animation: &Vec<&str>, mut window: &mut piston_window::PistonWindow) -> Player {
-		let size = Vec2::new(sx, sy);
-		let pos = Vec2::new(px, py);
-		let velocity = Vec2::new(0., 0.);
-		let animation = crate::render::PlayerAnimation::new(&mut window, animation);
+	pub fn new(movement_path: Option<String>) -> Player {
 		Player {
-			size,
-			pos,
-			velocity,
-			on_ground: false,
-			rotation: 0.0,
-			color: [1.0, 1.0, 1.0, 1.0],
-			texture: crate::mob::Animations::Stand,
-			animation,
-			ani_length: 0.0,
 			state: crate::mob::MobState::new(),
+			movement: MovementProfile::default(),
+			movement_source: None,
+			abilities: Abilities::default(),
+			movement_path,
 		}
 	}
-	pub fn play_animation(&mut self, ani: crate::mob::Animations, length: f64) {
-		match ani {
-			crate::mob::Animations::Jump => {
-				self.texture = crate::mob::Animations::Jump;
-			},
-			crate::mob::Animations::Walk1 => {
-				self.texture = crate::mob::Animations::Walk1;
-			},
-			_ => {
-				self.texture = crate::mob::Animations::Stand;
+	pub fn set_movement(&mut self, mut source: ProfileSource) {
+		self.movement = source.load();
+		self.movement_source = Some(source);
+	}
+	// Switch to the movement profile file a replay was recording with at this point.
+	pub fn replay_movement(&mut self, file: MovementProfile) {
+		if let Some(source) = &mut self.movement_source {
+			if source.file != file {
+				source.file = file;
+				self.movement = source.load();
+			}
+		}
+	}
+	// Reload the movement profile if its file has changed since it was loaded.
+	pub fn reload_movement(&mut self) {
+		if let Some(source) = &mut self.movement_source {
+			if source.changed() {
+				self.movement = source.load();
+				println!("\nReloaded movement profile");
+			}
+		}
+	}
+	// Sends splash events when entering or leaving liquids and runs out of breath
+	// while the head is under the surface.
+	fn update_liquid(&mut self, body: &mut Body, world: &crate::World, events: &mut Vec<GameEvent>, args: &piston::UpdateArgs) {
+		let in_liquid = world.liquids.iter().any(|l| l.area.overlaps(&body.transform.pos, &body.transform.size));
+		if in_liquid != self.state.in_liquid {
+			self.state.in_liquid = in_liquid;
+			events.push(GameEvent::Splash { pos: Vec2::new(body.transform.pos.x, body.transform.pos.y+body.transform.size.y/2.), entered: in_liquid });
+		}
+
+		let head = Vec2::new(body.transform.pos.x, body.transform.pos.y-body.transform.size.y/2.);
+		if !world.liquids.iter().any(|l| l.area.contains(&head)) {
+			self.state.breath = self.movement.breath_time;
+			return
+		}
+		if self.state.breath > 0. {
+			self.state.breath -= args.dt;
+			if self.state.breath <= 0. {
+				self.hurt(body, &Damage::lethal(), events);
+			}
+		}
+	}
+	// Grabs onto and moves along ladders and ropes, returns true while climbing.
+	fn update_climbing(&mut self, body: &mut Body, keystate: &crate::Keys, jump_pressed: bool, world: &crate::World, args: &piston::UpdateArgs) -> bool {
+		let movement = self.movement;
+		self.state.climb_cooldown -= args.dt;
+		let climbable = match world.climbables.iter().find(|c| c.area.overlaps(&body.transform.pos, &body.transform.size)) {
+			Some(climbable) => climbable,
+			None => {
+				self.state.climbing = false;
+				return false
 			},
+		};
+
+		if !self.state.climbing {
+			// Grab on by pressing up or down, unless trying to get down while standing.
+			if (keystate.w || keystate.s) && !(body.collider.on_ground && keystate.s) && self.state.climb_cooldown <= 0. {
+				self.state.climbing = true;
+				self.state.ground_pounding = false;
+				self.state.jumping = false;
+			}else {
+				return false
+			}
+		}
+
+		// Jump off, sideways if a direction is held.
+		if jump_pressed {
+			self.state.climbing = false;
+			self.state.climb_cooldown = 0.25;
+			self.state.jumping = true;
+			self.state.jump_buffer_timer = 0.;
+			body.velocity.y = -movement.climb_jump_velocity;
+			body.velocity.x = (keystate.d as i32 - keystate.a as i32) as f64*movement.climb_jump_push;
+			return true
+		}
+		// Let go when reaching the ground.
+		if body.collider.on_ground && keystate.s {
+			self.state.climbing = false;
+			return false
+		}
+
+		body.velocity.y = (keystate.s as i32 - keystate.w as i32) as f64*movement.climb_speed;
+		if climbable.rope {
+			body.velocity.x = 0.;
+			body.transform.pos.x = climbable.area.pos.x;
+		}else {
+			body.velocity.x = (keystate.d as i32 - keystate.a as i32) as f64*movement.climb_speed*0.5;
 		}
-		self.ani_length = length;
+		if keystate.d != keystate.a {
+			body.transform.facing = if keystate.d { 1.0 } else { -1.0 };
+		}
+		self.state.air_jumps_left = self.abilities.air_jumps;
+		true
 	}
-	pub fn update(&mut self, keystate: &mut crate::Keys, world: &crate::World, camera: &mut crate::Camera, collidables: &Vec<crate::collision::Cube>, args: &piston::UpdateArgs) {
-        // Controls
-        if keystate.space && self.on_ground{
-            self.velocity.y = -5.;
-            self.on_ground = false;
-            //self.play_animation(crate::mob::Animations::Jump, 0.6);
-        }
-        if keystate.d {
-            if self.on_ground {
-                self.velocity.x += world.grav_const*2.*args.dt;
-                self.velocity.x = self.velocity.x.clamp(-5., 5.);
-            }else {
-                self.velocity.x += world.grav_const*0.5*args.dt;
-                self.velocity.x = self.velocity.x.clamp(-5.,5.);
-            }
-            if self.velocity.x < 0. {
-                self.velocity.x -= self.velocity.x*0.1;
-            }
-            self.state.look_dir = 1.0;
-        }else
-        if keystate.a {
-            if self.on_ground {
-                self.velocity.x -= world.grav_const*2.*args.dt;
-                self.velocity.x = self.velocity.x.clamp(-5., 5.);
-            }else {
-                self.velocity.x -= world.grav_const*0.5*args.dt;
-                self.velocity.x = self.velocity.x.clamp(-5., 5.);
-            }
-            if self.velocity.x > 0. {
-                self.velocity.x -= self.velocity.x*0.1;
-            }
-            self.state.look_dir = -1.0;
-        }else {
-            self.velocity.x -= self.velocity.x*0.1;
-        }
+	// Starts and runs the dash and ground pound abilities, returns true while
+	// one of them is controlling the movement.
+	fn update_abilities(&mut self, body: &mut Body, keystate: &crate::Keys, jump_pressed: bool, args: &piston::UpdateArgs) -> bool {
+		let movement = self.movement;
+		let dash_pressed = keystate.shift && !self.state.dash_held;
+		let pound_pressed = keystate.pound && !self.state.ground_pound_held;
 
-        // Add gravity and subtract air resistance
-        self.velocity.y += world.grav_const*args.dt;
-        self.velocity.y -= self.velocity.y*(self.state.air_resistance.clamp(0.,1.));
+		if dash_pressed && self.abilities.dash && self.state.dash_cooldown <= 0. {
+			// Dash in the held direction, or the way the player is looking.
+			let mut dir = Vec2::new(keystate.d as i32 as f64 - keystate.a as i32 as f64,
+			                        keystate.s as i32 as f64 - keystate.w as i32 as f64);
+			if dir.x == 0. && dir.y == 0. {
+				dir.x = body.transform.facing;
+			}
+			let length = (dir.x*dir.x+dir.y*dir.y).sqrt();
+			self.state.dash_dir = Vec2::new(dir.x/length, dir.y/length);
+			self.state.dash_timer = movement.dash_time;
+			self.state.dash_cooldown = movement.dash_cooldown;
+			self.state.invulnerable_timer = self.state.invulnerable_timer.max(movement.dash_invulnerability);
+			self.state.ground_pounding = false;
+		}
+		if self.state.dash_timer > 0. {
+			self.state.dash_timer -= args.dt;
+			if self.state.dash_timer > 0. {
+				body.velocity.x = self.state.dash_dir.x*movement.dash_speed;
+				body.velocity.y = self.state.dash_dir.y*movement.dash_speed;
+				return true
+			}
+			// Don't keep the dash speed after the dash.
+			body.velocity.x = body.velocity.x.clamp(-movement.max_speed, movement.max_speed);
+			body.velocity.y = body.velocity.y.max(-movement.jump_velocity);
+		}
 
-        // Move mob
-        self.pos.y += self.velocity.y*METER_IN_PIXELS*args.dt;
-        self.pos.x += self.velocity.x*METER_IN_PIXELS*args.dt;
+		if pound_pressed && !jump_pressed && self.abilities.ground_pound && !body.collider.on_ground && !self.state.on_wall {
+			self.state.ground_pounding = true;
+			self.state.jumping = false;
+		}
+		if self.state.ground_pounding {
+			if body.collider.on_ground {
+				self.state.ground_pounding = false;
+				return false
+			}
+			body.velocity.x = 0.;
+			body.velocity.y = movement.ground_pound_speed;
+			return true
+		}
+		false
+	}
+	// Takes damage and gets knocked back, unless invulnerable. Sends a Died event
+	// when out of health, returns true if the damage was taken.
+	pub fn hurt(&mut self, body: &mut Body, damage: &Damage, events: &mut Vec<GameEvent>) -> bool {
+		// Dashing only protects against damage that doesn't kill right away.
+		if self.state.invulnerable_timer > 0. && damage.amount < i32::MAX {
+			return false
+		}
+		if !body.health.damage(damage) {
+			return false
+		}
+		if damage.knockback.x != 0. || damage.knockback.y != 0. {
+			*body.velocity = damage.knockback;
+			self.state.dash_timer = 0.;
+			self.state.ground_pounding = false;
+			self.state.climbing = false;
+			self.state.jumping = false;
+		}
+		if body.health.dead() {
+			events.push(GameEvent::Died);
+		}
+		true
+	}
+	// Bounce off a mob that was stomped on.
+	pub fn bounce(&mut self, body: &mut Body) {
+		body.velocity.y = -self.movement.jump_velocity*0.8;
+		self.state.ground_pounding = false;
+	}
+	// Put the player back at a checkpoint, healed and standing still.
+	pub fn respawn(&mut self, body: &mut Body, pos: Vec2) {
+		body.transform.pos = pos;
+		body.transform.facing = 1.;
+		*body.velocity = Vec2::new(0., 0.);
+		body.collider.on_ground = false;
+		self.state = MobState::new();
+		self.state.fall_top = pos.y;
+		body.health.restore();
+	}
+	// Fire in the direction the player is looking while fire is held.
+	fn update_fire(&mut self, body: &mut Body, keystate: &crate::Keys, events: &mut Vec<GameEvent>, args: &piston::UpdateArgs) {
+		self.state.fire_cooldown -= args.dt;
+		if !keystate.fire || self.state.fire_cooldown > 0. || self.state.climbing {
+			return
+		}
+		self.state.fire_cooldown = SHOT_COOLDOWN;
+		let look_dir = body.transform.facing;
+		events.push(GameEvent::Shoot(Shot {
+			pos: Vec2::new(body.transform.pos.x+look_dir*body.transform.size.x/2., body.transform.pos.y),
+			velocity: Vec2::new(look_dir*SHOT_SPEED, 0.),
+			lifetime: SHOT_LIFETIME,
+			gravity: false,
+			damage: 1,
+			mask: projectile::LAYER_TERRAIN | projectile::LAYER_MOB,
+			size: 6.,
+			color: PLAYER_SHOT_COLOR,
+		}));
+	}
+	// Hurt by hazards, long falls and falling out of the world.
+	fn update_damage(&mut self, body: &mut Body, world: &crate::World, events: &mut Vec<GameEvent>, args: &piston::UpdateArgs) {
+		body.health.update(args.dt);
 
-        // Walljump cooldown
-        if self.state.walljump {
-            self.state.walljump_time -= args.dt;
-            if self.state.walljump_time <= 0. {
-                self.state.walljump = false;
-            }
-        }
+		if body.collider.on_ground {
+			let fall = (body.transform.pos.y-self.state.fall_top)/METER_IN_PIXELS-self.movement.safe_fall_height;
+			if fall > 0. {
+				let amount = (fall*self.movement.fall_damage).ceil() as i32;
+				self.hurt(body, &Damage { amount, knockback: Vec2::new(0., 0.) }, events);
+			}
+		}
+		// Climbing and swimming break falls too.
+		if body.collider.on_ground || self.state.climbing || self.state.in_liquid {
+			self.state.fall_top = body.transform.pos.y;
+		}else {
+			self.state.fall_top = self.state.fall_top.min(body.transform.pos.y);
+		}
 
-        // Collision detection
-        use crate::collision::{cube_collider, Side};
-        for obj in collidables {
-            if cube_collider(self, obj, Side::North) {
-                self.velocity.y = if self.velocity.y > 0. { 0. }
-                                 else { self.velocity.y };
-                self.pos.y = obj.pos.y-(obj.size.y+self.size.y)/2.;
-                self.on_ground = true;
-            }else
-            if cube_collider(self, obj, Side::East) {
-                self.velocity.x = if self.velocity.x < 0. { 0. }
-                                 else { self.velocity.x };
-                self.pos.x = obj.pos.x+(obj.size.x+self.size.x)/2.;
+		for hazard in &world.hazards {
+			if hazard.area.overlaps(&body.transform.pos, &body.transform.size) {
+				self.hurt(body, &Damage::from(hazard.damage, &hazard.area.pos, &body.transform.pos, KNOCKBACK), events);
+			}
+		}
+		if crate::health::out_of_world(&body.transform.pos, world) {
+			self.hurt(body, &Damage::lethal(), events);
+		}
+	}
+	// Resolve a collision with a single solid object, returns the side of the
+	// object that was hit.
+	fn collide(&mut self, body: &mut Body, obj: &Cube, keystate: &mut crate::Keys, world: &crate::World, args: &piston::UpdateArgs) -> Option<Side> {
+        let side = physics::resolve(&mut body.transform.pos, &body.transform.size, body.velocity, obj)?;
+        match side {
+            Side::North => {
+                body.collider.on_ground = body.velocity.y >= 0.;
+                self.state.ground = obj.material;
+            },
+            Side::East | Side::West => {
+                // Walls push the player away to the east or the west.
+                let away = if let Side::East = side { 1. } else { -1. };
+                self.state.on_wall = true;
 
                 // Add friction on wall
-                self.velocity.y -= self.velocity.y.clamp(-0.06,100.)*obj.friction*args.dt;
+                body.velocity.y -= body.velocity.y.clamp(-0.06,100.)*(WALL_FRICTION*obj.material.wall_grip*args.dt).min(1.);
                 // Slide down wall
                 if keystate.s {
-                    self.velocity.y *= 2.;
-                    self.velocity.y = self.velocity.y.clamp(0., 3.6);
+                    body.velocity.y *= 2.;
+                    body.velocity.y = body.velocity.y.clamp(0., self.movement.wall_slide_speed);
                 }
-                
-                // Walljump!
-                if keystate.space && !self.state.walljump {
-                    self.velocity.x = world.grav_const*10.*args.dt;
-                    self.velocity.y = -3.;
+
+                // Walljump! Only possible on walls with grip.
+                if keystate.space && !self.state.walljump && obj.material.wall_grip > 0. {
+                    body.velocity.x = away*world.grav_const*self.movement.walljump_push*args.dt;
+                    body.velocity.y = -self.movement.walljump_velocity;
                     keystate.space = false;
                     self.state.walljump = true;
                     self.state.walljump_time = 1.0;
@@ -172,78 +364,299 @@ impl Player {
                 }else if !keystate.space {
                     self.state.walljump = false;
                 }
-            }else
-            if cube_collider(self, obj, Side::West) {
-                self.velocity.x = if self.velocity.x > 0. { 0. }
-                                 else { self.velocity.x };
-                self.pos.x = obj.pos.x-(obj.size.x+self.size.x)/2.;
+            },
+            Side::South => (),
+        }
+        Some(side)
+	}
+	pub fn update(&mut self, body: &mut Body, mut animation: Option<&mut Animation>, frame: &mut Frame) {
+        let (keystate, world, collidables) = (&mut *frame.keystate, frame.world, frame.collidables);
+        let (events, args) = (&mut *frame.events, frame.args);
+        self.update_liquid(body, world, events, args);
+        let liquid = world.liquids.iter().find(|l| l.area.overlaps(&body.transform.pos, &body.transform.size));
 
-                // Add friction on wall
-                self.velocity.y -= self.velocity.y.clamp(-0.06,100.)*obj.friction*args.dt;
-                // Slide down wall
-                if keystate.s {
-                    self.velocity.y *= 2.;
-                    self.velocity.y = self.velocity.y.clamp(0., 3.6);
+        // Controls
+        let movement = self.movement;
+        let jump_pressed = keystate.space && !self.state.jump_held;
+        let jump_released = !keystate.space && self.state.jump_held;
+        self.state.jump_held = keystate.space;
+
+        // Allow jumping a little while after walking off a ledge, and remember
+        // jump presses for a little while before landing.
+        if body.collider.on_ground {
+            self.state.coyote_timer = movement.coyote_time;
+            self.state.air_jumps_left = self.abilities.air_jumps;
+        }else {
+            self.state.coyote_timer -= args.dt;
+        }
+        if jump_pressed {
+            self.state.jump_buffer_timer = movement.jump_buffer;
+        }else {
+            self.state.jump_buffer_timer -= args.dt;
+        }
+
+        // Ability timers run and held keys are remembered even while climbing, so
+        // letting go of a ladder with a key held doesn't fire its ability.
+        self.state.dash_cooldown -= args.dt;
+        self.state.invulnerable_timer -= args.dt;
+
+        // Climbing and abilities take over the controls and gravity while they are active.
+        if !self.update_climbing(body, keystate, jump_pressed, world, args) &&
+           !self.update_abilities(body, keystate, jump_pressed, args) {
+            if liquid.is_some() {
+                // Every jump press is a swim stroke while in a liquid.
+                if jump_pressed {
+                    body.velocity.y = -movement.swim_stroke_velocity;
+                    self.state.jump_buffer_timer = 0.;
                 }
+            }else if self.state.jump_buffer_timer > 0. && self.state.coyote_timer > 0. {
+                body.velocity.y = -movement.jump_velocity;
+                body.collider.on_ground = false;
+                self.state.jumping = true;
+                self.state.jump_buffer_timer = 0.;
+                self.state.coyote_timer = 0.;
+            }else if jump_pressed && !self.state.on_wall && self.state.air_jumps_left > 0 {
+                body.velocity.y = -movement.air_jump_velocity;
+                self.state.jumping = true;
+                self.state.jump_buffer_timer = 0.;
+                self.state.air_jumps_left -= 1;
+                if let Some(animation) = &mut animation {
+                    animation.set(Flag::AirJump, true);
+                }
+            }
 
-                // Walljump!
-                if keystate.space && !self.state.walljump {
-                    self.velocity.x = -world.grav_const*10.*args.dt;
-                    self.velocity.y = -3.;
-                    keystate.space = false;
-                    self.state.walljump = true;
-                    self.state.walljump_time = 1.0;
-                }else if keystate.space {
-                    self.state.walljump_time -= 2.5*args.dt;
-                }else if !keystate.space {
-                    self.state.walljump = false;
+            // Releasing jump early cuts the jump short.
+            if self.state.jumping && (jump_released || body.velocity.y >= 0.) {
+                if jump_released && body.velocity.y < 0. {
+                    body.velocity.y *= movement.jump_cut;
                 }
-            }else
-            if cube_collider(self, obj, Side::South) {
-                self.velocity.y = if self.velocity.y < 0. { 0. }
-                                 else { self.velocity.y };
-                self.pos.y = obj.pos.y+(obj.size.y+self.size.y)/2.;
+                self.state.jumping = false;
             }
+
+            // Slippery ground gives less grip, both to speed up and to slow down.
+            let traction = if body.collider.on_ground { self.state.ground.friction } else { 1. };
+            let acceleration = if liquid.is_some() { movement.swim_acceleration }
+                               else if body.collider.on_ground { movement.run_acceleration*traction.min(1.) }
+                               else { movement.air_control };
+            let damping = (movement.ground_damping*traction).clamp(0., 1.);
+            if keystate.d {
+                body.velocity.x += world.grav_const*acceleration*args.dt;
+                body.velocity.x = body.velocity.x.clamp(-movement.max_speed, movement.max_speed);
+                if body.velocity.x < 0. {
+                    body.velocity.x -= body.velocity.x*damping;
+                }
+                body.transform.facing = 1.0;
+            }else if keystate.a {
+                body.velocity.x -= world.grav_const*acceleration*args.dt;
+                body.velocity.x = body.velocity.x.clamp(-movement.max_speed, movement.max_speed);
+                if body.velocity.x > 0. {
+                    body.velocity.x -= body.velocity.x*damping;
+                }
+                body.transform.facing = -1.0;
+            }else {
+                body.velocity.x -= body.velocity.x*damping;
+            }
+
+            // Add gravity and subtract air resistance, liquids replace air resistance with their drag
+            physics::apply_gravity(body.velocity, liquid, world, AIR_RESISTANCE, args.dt);
         }
 
-        camera.position.x = (self.pos.x-camera.w)*camera.zoom+(camera.w*camera.zoom)/2.;
-        camera.position.y = (self.pos.y-camera.h)*camera.zoom+(camera.h*camera.zoom)/2.;
+        self.state.dash_held = keystate.shift;
+        self.state.ground_pound_held = keystate.pound;
+
+        // Ride along with the platform stood on during the last update.
+        physics::ride(&mut body.transform.pos, self.state.riding, world);
+
+        // Move mob
+        physics::move_body(&mut body.transform.pos, body.velocity, args.dt);
+
+        // Walljump cooldown
+        if self.state.walljump {
+            self.state.walljump_time -= args.dt;
+            if self.state.walljump_time <= 0. {
+                self.state.walljump = false;
+            }
+        }
 
-        self.ani_length -= args.dt;
-        if self.ani_length < 0.0 && self.velocity.y == 0.0 && (self.velocity.x > 0.1 || self.velocity.x < -0.1) {
-        	match self.texture {
-        		crate::mob::Animations::Stand => self.play_animation(crate::mob::Animations::Walk1, 0.2),
-        		crate::mob::Animations::Walk1 => self.play_animation(crate::mob::Animations::Stand, 0.2),
-        		_ => (),
-        	}
+        // Collision detection, on_ground is only true while standing on something.
+        body.collider.on_ground = false;
+        self.state.on_wall = false;
+        for obj in collidables {
+            self.collide(body, obj, keystate, world, args);
+        }
+        for door in world.doors.iter().filter(|d| !d.open) {
+            self.collide(body, &door.cube, keystate, world, args);
+        }
+
+        // Moving platforms carry players standing on them and push the ones in their way.
+        self.state.riding = None;
+        // The sides of the platforms the player was pushed away from.
+        let mut pushed = Vec::new();
+        for (i, platform) in world.platforms.iter().enumerate() {
+            let side = self.collide(body, &platform.cube, keystate, world, args);
+            if let Some(Side::North) = side {
+                self.state.riding = Some(i);
+            }
+            pushed.extend(side);
+        }
+        // Getting pushed into the terrain or a closed door on the other side of the
+        // player crushes it, being pushed along a wall doesn't.
+        let pos = body.transform.pos;
+        let inner = Vec2::new(body.transform.size.x-2.*CRUSH_MARGIN, body.transform.size.y-2.*CRUSH_MARGIN);
+        let doors = world.doors.iter().filter(|d| !d.open).map(|d| &d.cube);
+        let crushed = collidables.iter().chain(doors).any(|obj| {
+            let opposite = pushed.iter().any(|side| match side {
+                Side::North => obj.pos.y < pos.y,
+                Side::South => obj.pos.y > pos.y,
+                Side::East => obj.pos.x > pos.x,
+                Side::West => obj.pos.x < pos.x,
+            });
+            opposite && Rect::new(obj.pos, obj.size).overlaps(&pos, &inner)
+        });
+        if crushed {
+            self.hurt(body, &Damage::lethal(), events);
+        }
+
+        // Conveyors carry whatever stands on them.
+        if body.collider.on_ground {
+            body.transform.pos.x += self.state.ground.surface_velocity*METER_IN_PIXELS*args.dt;
+        }
+
+        self.update_damage(body, world, events, args);
+        self.update_fire(body, keystate, events, args);
+
+        // What the player is doing besides moving, for the transitions of its animator.
+        if let Some(animation) = animation {
+            animation.set(Flag::OnWall, self.state.on_wall);
+            animation.set(Flag::Climbing, self.state.climbing);
+            animation.set(Flag::Swimming, self.state.in_liquid);
+            animation.set(Flag::Dashing, self.state.dash_timer > 0.);
+            animation.set(Flag::GroundPounding, self.state.ground_pounding);
         }
 	}
-	pub fn render(&mut self, camera: &crate::Camera, window: &mut piston_window::PistonWindow, e: &piston::Event) {
-		let (x, y) = (self.pos.x, self.pos.y);
-		let (w, h) = (self.size.x, self.size.y);
-		let look_dir = &self.state.look_dir;
-		let texture = match self.texture {
-			crate::mob::Animations::Jump => &self.animation.jump,
-			crate::mob::Animations::Walk1 => &self.animation.walk1,
-			crate::mob::Animations::Stand => &self.animation.stand,
-			crate::mob::Animations::None => &self.animation.stand,
-			_ => panic!("Invalid animation!"),
+}
+
+// An enemy moved by its AI controller, falling and colliding like the player.
+pub struct Mob {
+	riding: Option<usize>,
+	// Top speed in meters per second.
+	speed: f64,
+	jump_velocity: f64,
+	controller: crate::ai::Controller,
+	// Seconds between shots at the player, mobs that don't shoot have 0.
+	shoot_interval: f64,
+	shoot_timer: f64,
+	// How close the player must be to get shot at, in pixels.
+	shoot_range: f64,
+	shot_speed: f64,
+	// Lobbed shots fall with gravity, the others fly straight.
+	shot_gravity: bool,
+}
+
+impl Mob {
+	// Read the properties of a map object or prefab, ranges are given in tiles.
+	pub fn from_properties(properties: &HashMap<String, String>, home: Vec2) -> Mob {
+		let property = |name: &str, default: f64| properties.get(name).and_then(|v| v.parse().ok()).unwrap_or(default);
+		Mob {
+			riding: None,
+			speed: property("speed", 2.),
+			jump_velocity: property("jump", 5.),
+			controller: crate::ai::Controller::from_properties(properties, home),
+			shoot_interval: property("shoot", 0.),
+			shoot_timer: 0.,
+			shoot_range: property("shoot_range", 8.)*METER_IN_PIXELS,
+			shot_speed: property("shot_speed", 6.),
+			shot_gravity: properties.get("shot_gravity").map(|g| g == "true").unwrap_or(false),
+		}
+	}
+	pub fn update(&mut self, body: &mut Body, world: &crate::World, collidables: &[Cube], target: Option<Vec2>, events: &mut Vec<GameEvent>, dt: f64) {
+		body.health.update(dt);
+		for hazard in &world.hazards {
+			if hazard.area.overlaps(&body.transform.pos, &body.transform.size) {
+				body.hurt(&Damage::from(hazard.damage, &hazard.area.pos, &body.transform.pos, KNOCKBACK));
+			}
+		}
+		if crate::health::out_of_world(&body.transform.pos, world) {
+			body.hurt(&Damage::lethal());
+			return
+		}
+
+		// Probe just in front of the mob for walls and for the edge of the floor.
+		let ahead = body.transform.pos.x+body.transform.facing*(body.transform.size.x/2.+2.);
+		let senses = crate::ai::Senses {
+			pos: body.transform.pos,
+			look_dir: body.transform.facing,
+			on_ground: body.collider.on_ground,
+			ground_ahead: physics::solid_at(&Vec2::new(ahead, body.transform.pos.y+body.transform.size.y/2.+2.), collidables, world),
+			wall_ahead: physics::solid_at(&Vec2::new(ahead, body.transform.pos.y), collidables, world),
+			target,
 		};
+		let intent = self.controller.think(&senses, dt);
+		if intent.run != 0. {
+			body.transform.facing = intent.run.signum();
+		}
 
-		let scale = &camera.zoom;
-		let offset = &camera.position;
-		let (app_w, app_h) = (&camera.w, &camera.h);
-
-		use crate::piston_window::{image,ImageSize};
-		use crate::graphics::Transformed;
-		window.draw_2d(e, |c, g, _| {
-			image(texture,
-				  c.transform
-				   .trans((x-(w*look_dir)/2.-app_w/2.)*scale+app_w/2.-offset.x,
-				   		  (y-h/2.-app_h/2.)*scale+app_h/2.-offset.y)
-				   .scale((look_dir*w/texture.get_width() as f64)*scale,
-				   		  (h/texture.get_height() as f64)*scale)
-			, g);
-		});
-	}
-}
\ No newline at end of file
+		// Ease towards the wanted velocity instead of snapping to it.
+		let ease = (4.*dt).min(1.);
+		match intent.fly {
+			Some(dir) => {
+				body.velocity.x += (dir.x*self.speed-body.velocity.x)*ease;
+				body.velocity.y += (dir.y*self.speed-body.velocity.y)*ease;
+			},
+			None => {
+				body.velocity.x += (intent.run*self.speed-body.velocity.x)*ease;
+				if intent.jump && body.collider.on_ground {
+					body.velocity.y = -self.jump_velocity;
+				}
+				let liquid = world.liquids.iter().find(|l| l.area.overlaps(&body.transform.pos, &body.transform.size));
+				physics::apply_gravity(body.velocity, liquid, world, 0., dt);
+			},
+		}
+
+		physics::ride(&mut body.transform.pos, self.riding, world);
+		physics::move_body(&mut body.transform.pos, body.velocity, dt);
+
+		body.collider.on_ground = false;
+		self.riding = None;
+		let doors = world.doors.iter().filter(|d| !d.open).map(|d| &d.cube);
+		for obj in collidables.iter().chain(doors) {
+			if let Some(Side::North) = physics::resolve(&mut body.transform.pos, &body.transform.size, body.velocity, obj) {
+				body.collider.on_ground = true;
+			}
+		}
+		for (i, platform) in world.platforms.iter().enumerate() {
+			if let Some(Side::North) = physics::resolve(&mut body.transform.pos, &body.transform.size, body.velocity, &platform.cube) {
+				body.collider.on_ground = true;
+				self.riding = Some(i);
+			}
+		}
+
+		if let Some(target) = target {
+			self.update_shooting(body.transform.pos, target, events, dt);
+		}
+	}
+	// Shoot at the target every interval while it is in range.
+	fn update_shooting(&mut self, pos: Vec2, target: Vec2, events: &mut Vec<GameEvent>, dt: f64) {
+		if self.shoot_interval <= 0. {
+			return
+		}
+		self.shoot_timer -= dt;
+		let (dx, dy) = (target.x-pos.x, target.y-pos.y);
+		if self.shoot_timer > 0. || dx*dx+dy*dy > self.shoot_range*self.shoot_range {
+			return
+		}
+		self.shoot_timer = self.shoot_interval;
+		// Aim above the target when lobbing, further away needs a higher arc.
+		let aim_at = if self.shot_gravity { Vec2::new(target.x, target.y-dx.abs()*0.5) } else { target };
+		events.push(GameEvent::Shoot(Shot {
+			pos,
+			velocity: projectile::aim(&pos, &aim_at, self.shot_speed),
+			lifetime: 3.,
+			gravity: self.shot_gravity,
+			damage: 1,
+			mask: projectile::LAYER_TERRAIN | projectile::LAYER_PLAYER,
+			size: 8.,
+			color: MOB_SHOT_COLOR,
+		}));
+	}
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+use serde::{Serialize, Deserialize};
+
+// Everything that decides how a character moves. Velocities are in meters per
+// second, accelerations (run_acceleration, air_control and walljump_push) are
+// multiples of the worlds gravity.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct MovementProfile {
+	pub jump_velocity: f64,
+	pub walljump_push: f64,
+	pub walljump_velocity: f64,
+	pub run_acceleration: f64,
+	pub air_control: f64,
+	pub max_speed: f64,
+	pub ground_damping: f64,
+	pub wall_slide_speed: f64,
+	// Vertical velocity is multiplied by this when jump is released while rising.
+	pub jump_cut: f64,
+	// Seconds after leaving a ledge where jumping is still allowed.
+	pub coyote_time: f64,
+	// Seconds a jump press is remembered before landing.
+	pub jump_buffer: f64,
+	pub air_jump_velocity: f64,
+	pub dash_speed: f64,
+	pub dash_time: f64,
+	pub dash_cooldown: f64,
+	// Seconds the player can't be hurt after starting a dash.
+	pub dash_invulnerability: f64,
+	pub ground_pound_speed: f64,
+	pub climb_speed: f64,
+	pub climb_jump_velocity: f64,
+	pub climb_jump_push: f64,
+	pub swim_stroke_velocity: f64,
+	pub swim_acceleration: f64,
+	// Seconds the character can stay under water.
+	pub breath_time: f64,
+	// Meters the character can fall without getting hurt.
+	pub safe_fall_height: f64,
+	// Damage per meter fallen beyond the safe height.
+	pub fall_damage: f64,
+}
+
+impl Default for MovementProfile {
+	fn default() -> MovementProfile {
+		MovementProfile {
+			jump_velocity: 5.,
+			walljump_push: 10.,
+			walljump_velocity: 3.,
+			run_acceleration: 2.,
+			air_control: 0.5,
+			max_speed: 5.,
+			ground_damping: 0.1,
+			wall_slide_speed: 3.6,
+			jump_cut: 0.5,
+			coyote_time: 0.1,
+			jump_buffer: 0.1,
+			air_jump_velocity: 4.5,
+			dash_speed: 12.,
+			dash_time: 0.15,
+			dash_cooldown: 0.6,
+			dash_invulnerability: 0.25,
+			ground_pound_speed: 12.,
+			climb_speed: 3.,
+			climb_jump_velocity: 4.,
+			climb_jump_push: 3.,
+			swim_stroke_velocity: 3.,
+			swim_acceleration: 1.,
+			breath_time: 8.,
+			safe_fall_height: 8.,
+			fall_damage: 1.,
+		}
+	}
+}
+
+// Prefix of map properties that override values of the movement profile,
+// ex. "movement.jump_velocity".
+const OVERRIDE_PREFIX: &str = "movement.";
+
+// The file a movement profile is loaded from, together with the overrides of
+// the current map. Keeps track of when the file was last changed so the
+// profile can be reloaded while the game is running.
+pub struct ProfileSource {
+	path: String,
+	overrides: HashMap<String, f64>,
+	modified: Option<SystemTime>,
+	// The profile as it was read from the file, before the overrides.
+	pub file: MovementProfile,
+	// Replays bring the file as it was recorded, it isn't read then.
+	recorded: bool,
+}
+
+impl ProfileSource {
+	pub fn new(path: &str, map_properties: &HashMap<String, String>) -> ProfileSource {
+		let mut overrides = HashMap::new();
+		for (name, value) in map_properties {
+			if let Some(field) = name.strip_prefix(OVERRIDE_PREFIX) {
+				match value.parse() {
+					Ok(value) => { overrides.insert(String::from(field), value); },
+					Err(_) => println!("Map property {:?} is not a number!", name),
+				}
+			}
+		}
+		ProfileSource {
+			path: String::from(path),
+			overrides,
+			modified: None,
+			file: MovementProfile::default(),
+			recorded: false,
+		}
+	}
+	// A source using the given profile instead of reading the file.
+	pub fn recorded(path: &str, file: MovementProfile, map_properties: &HashMap<String, String>) -> ProfileSource {
+		ProfileSource {
+			file,
+			recorded: true,
+			..ProfileSource::new(path, map_properties)
+		}
+	}
+	// Load the profile, falling back to the default profile if the file is missing or invalid.
+	pub fn load(&mut self) -> MovementProfile {
+		if self.recorded {
+			return self.apply_overrides(self.file)
+		}
+		self.modified = modified_time(&self.path);
+		self.file = match std::fs::read_to_string(&self.path) {
+			Ok(text) => match toml::from_str(&text) {
+				Ok(profile) => profile,
+				Err(e) => {
+					println!("Invalid movement profile {:?}: {}", self.path, e);
+					MovementProfile::default()
+				},
+			},
+			Err(e) => {
+				println!("Could not read movement profile {:?}: {}", self.path, e);
+				MovementProfile::default()
+			},
+		};
+		self.apply_overrides(self.file)
+	}
+	pub fn changed(&self) -> bool {
+		!self.recorded && modified_time(&self.path) != self.modified
+	}
+	fn apply_overrides(&self, profile: MovementProfile) -> MovementProfile {
+		if self.overrides.is_empty() {
+			return profile
+		}
+		let mut table = match toml::Value::try_from(profile) {
+			Ok(toml::Value::Table(table)) => table,
+			_ => return profile,
+		};
+		for (name, value) in &self.overrides {
+			if table.contains_key(name) {
+				table.insert(name.clone(), toml::Value::Float(*value));
+			}else {
+				println!("Unknown movement value {:?} in map properties", name);
+			}
+		}
+		toml::Value::Table(table).try_into().unwrap_or(profile)
+	}
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+	std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
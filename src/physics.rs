@@ -0,0 +1,122 @@
+use crate::collision::{Cube, Liquid, Side, cube_collider};
+use crate::vector::Vec2;
+use crate::METER_IN_PIXELS;
+
+// Movement shared by everything that falls and collides with the terrain, used
+// by both players and mobs.
+
+// Add gravity and subtract air resistance, or the buoyancy and drag of the
+// liquid the body is in.
+pub fn apply_gravity(velocity: &mut Vec2, liquid: Option<&Liquid>, world: &crate::World, air_resistance: f64, dt: f64) {
+	match liquid {
+		Some(liquid) => {
+			let drag = (liquid.drag*dt).clamp(0.,1.);
+			velocity.y += world.grav_const*(1.-liquid.buoyancy)*dt;
+			velocity.y -= velocity.y*drag;
+			velocity.x -= velocity.x*drag;
+		},
+		None => {
+			velocity.y += world.grav_const*dt;
+			velocity.y -= velocity.y*(air_resistance.clamp(0.,1.));
+		},
+	}
+}
+
+pub fn move_body(pos: &mut Vec2, velocity: &Vec2, dt: f64) {
+	pos.y += velocity.y*METER_IN_PIXELS*dt;
+	pos.x += velocity.x*METER_IN_PIXELS*dt;
+}
+
+// Move and turn along with the platform stood on during the last update.
+pub fn ride(pos: &mut Vec2, riding: Option<usize>, world: &crate::World) {
+	if let Some(platform) = riding.and_then(|i| world.platforms.get(i)) {
+		// Where the body was from the center of the platform before it moved.
+		let center = platform.cube.pos;
+		let (x, y) = (pos.x-center.x+platform.delta.x, pos.y-center.y+platform.delta.y);
+		let (sin, cos) = platform.turn.sin_cos();
+		pos.x = center.x+x*cos-y*sin;
+		pos.y = center.y+x*sin+y*cos;
+	}
+}
+
+// Push a body out of a solid object and bounce it off the objects material.
+// Returns the side of the object that was hit.
+pub fn resolve(pos: &mut Vec2, size: &Vec2, velocity: &mut Vec2, obj: &Cube) -> Option<Side> {
+	if obj.rotation != 0. {
+		return resolve_rotated(pos, size, velocity, obj)
+	}
+	if cube_collider(pos, size, obj, Side::North) {
+		velocity.y = if velocity.y > 0. { -obj.material.bounce(velocity.y) }
+		             else { velocity.y };
+		pos.y = obj.pos.y-(obj.size.y+size.y)/2.;
+		return Some(Side::North)
+	}
+	if cube_collider(pos, size, obj, Side::East) {
+		velocity.x = if velocity.x < 0. { obj.material.bounce(velocity.x) }
+		             else { velocity.x };
+		pos.x = obj.pos.x+(obj.size.x+size.x)/2.;
+		return Some(Side::East)
+	}
+	if cube_collider(pos, size, obj, Side::West) {
+		velocity.x = if velocity.x > 0. { -obj.material.bounce(velocity.x) }
+		             else { velocity.x };
+		pos.x = obj.pos.x-(obj.size.x+size.x)/2.;
+		return Some(Side::West)
+	}
+	if cube_collider(pos, size, obj, Side::South) {
+		velocity.y = if velocity.y < 0. { 0. }
+		             else { velocity.y };
+		pos.y = obj.pos.y+(obj.size.y+size.y)/2.;
+		return Some(Side::South)
+	}
+	None
+}
+
+// Like resolve for an object turned by its rotation. The body is pushed out
+// along the axis it overlaps the object the least on, vertically when that
+// axis is closer to vertical so it doesn't slide down slopes it stands on.
+fn resolve_rotated(pos: &mut Vec2, size: &Vec2, velocity: &mut Vec2, obj: &Cube) -> Option<Side> {
+	let (sin, cos) = obj.rotation.sin_cos();
+	let (dx, dy) = (pos.x-obj.pos.x, pos.y-obj.pos.y);
+	let mut push: Option<(f64, Vec2)> = None;
+	for axis in [Vec2::new(1., 0.), Vec2::new(0., 1.), Vec2::new(cos, sin), Vec2::new(-sin, cos)].iter() {
+		let body = (size.x*axis.x.abs()+size.y*axis.y.abs())/2.;
+		let object = (obj.size.x*(axis.x*cos+axis.y*sin).abs()+obj.size.y*(axis.y*cos-axis.x*sin).abs())/2.;
+		let distance = dx*axis.x+dy*axis.y;
+		let overlap = body+object-distance.abs();
+		if overlap <= 0. {
+			return None
+		}
+		if push.map(|(o, _)| overlap < o).unwrap_or(true) {
+			let sign = if distance < 0. { -1. } else { 1. };
+			push = Some((overlap, Vec2::new(axis.x*sign, axis.y*sign)));
+		}
+	}
+	let (overlap, normal) = push?;
+	if normal.y.abs() >= normal.x.abs() {
+		pos.y += overlap/normal.y;
+		if normal.y < 0. {
+			velocity.y = if velocity.y > 0. { -obj.material.bounce(velocity.y) } else { velocity.y };
+			Some(Side::North)
+		}else {
+			velocity.y = if velocity.y < 0. { 0. } else { velocity.y };
+			Some(Side::South)
+		}
+	}else {
+		pos.x += overlap/normal.x;
+		if normal.x > 0. {
+			velocity.x = if velocity.x < 0. { obj.material.bounce(velocity.x) } else { velocity.x };
+			Some(Side::East)
+		}else {
+			velocity.x = if velocity.x > 0. { -obj.material.bounce(velocity.x) } else { velocity.x };
+			Some(Side::West)
+		}
+	}
+}
+
+// True if the terrain, a moving platform or a closed door covers the point.
+pub fn solid_at(point: &Vec2, collidables: &[Cube], world: &crate::World) -> bool {
+	collidables.iter().chain(world.platforms.iter().map(|p| &p.cube))
+	           .chain(world.doors.iter().filter(|d| !d.open).map(|d| &d.cube))
+	           .any(|obj| obj.contains(point))
+}
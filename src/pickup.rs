@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use crate::ability::Abilities;
+use crate::collision::{Cube, Rect};
+use crate::loader::MapObject;
+use crate::ecs::Transform;
+use crate::health::Health;
+use crate::mob::Player;
+use crate::vector::Vec2;
+
+// How far outside of a door the player can be and still open it, since the
+// player can't overlap a closed door.
+const DOOR_REACH: f64 = 2.;
+// Name of keys and locks that don't have one.
+const DEFAULT_KEY: &str = "gold";
+
+pub enum Item {
+	// Counted on the results screen.
+	Gem,
+	Coin(u32),
+	Key(String),
+	PowerUp(PowerUp),
+}
+
+// Power-ups last until the end of the level.
+pub enum PowerUp {
+	Dash,
+	AirJump,
+	GroundPound,
+	Health(i32),
+}
+
+impl Item {
+	// Objects of type "collectible", "coin", "key" and "powerup". Coins have a
+	// "value", keys a "key" name and power-ups say which power they give with "power".
+	pub fn from_object(obj: &MapObject) -> Option<Item> {
+		let property = |name: &str| obj.properties.get(name).map(|v| v.as_str());
+		let item = match obj.kind.as_str() {
+			"collectible" => Item::Gem,
+			"coin" => Item::Coin(property("value").and_then(|v| v.parse().ok()).unwrap_or(1)),
+			"key" => Item::Key(String::from(property("key").unwrap_or(DEFAULT_KEY))),
+			"powerup" => Item::PowerUp(match property("power") {
+				Some("dash") => PowerUp::Dash,
+				Some("air_jump") => PowerUp::AirJump,
+				Some("ground_pound") => PowerUp::GroundPound,
+				Some("health") => PowerUp::Health(property("amount").and_then(|v| v.parse().ok()).unwrap_or(1)),
+				power => {
+					println!("Unknown power-up {:?} of {:?}", power, obj.name);
+					return None
+				},
+			}),
+			_ => return None,
+		};
+		Some(item)
+	}
+	fn texture(&self) -> &'static str {
+		match self {
+			Item::Gem => "assets/sprites/gem.png",
+			Item::Coin(_) => "assets/sprites/coin.png",
+			Item::Key(_) => "assets/sprites/key.png",
+			Item::PowerUp(_) => "assets/sprites/powerup.png",
+		}
+	}
+}
+
+// What the player picked up during this run of the level.
+#[derive(Clone, Default)]
+pub struct Inventory {
+	pub coins: u32,
+	pub keys: HashMap<String, u32>,
+}
+
+impl Inventory {
+	pub fn key_count(&self) -> u32 {
+		self.keys.values().sum()
+	}
+}
+
+pub struct Pickup {
+	pub cube: Cube,
+	pub item: Item,
+	pub taken: bool,
+	// Taken before the last checkpoint, these stay taken after dying.
+	pub saved: bool,
+}
+
+impl Pickup {
+	pub fn from_object(obj: &MapObject, textures: &mut crate::asset::Textures) -> Option<Pickup> {
+		let item = Item::from_object(obj)?;
+		let texture = obj.properties.get("texture").map(|t| t.as_str()).unwrap_or(item.texture());
+		Some(Pickup {
+			cube: Cube::new(obj.size.x, obj.size.y, obj.pos.x, obj.pos.y, texture, textures),
+			item,
+			taken: false,
+			saved: false,
+		})
+	}
+	// Take the pickup if the player touches it, adding it to the inventory or
+	// giving its power to the player. Abilities are unlocked in the save too.
+	pub fn update(&mut self, transform: &Transform, player: &mut Player, health: &mut Health, inventory: &mut Inventory, unlocked: &mut Abilities) {
+		if self.taken || !Rect::new(self.cube.pos, self.cube.size).overlaps(&transform.pos, &transform.size) {
+			return
+		}
+		self.taken = true;
+		match &self.item {
+			Item::Gem => (),
+			Item::Coin(value) => inventory.coins += value,
+			Item::Key(key) => *inventory.keys.entry(key.clone()).or_insert(0) += 1,
+			Item::PowerUp(PowerUp::Dash) => {
+				player.abilities.dash = true;
+				unlocked.dash = true;
+			},
+			Item::PowerUp(PowerUp::AirJump) => {
+				player.abilities.air_jumps += 1;
+				// Taking the same power-up again after dying doesn't add another one to the save.
+				unlocked.air_jumps = unlocked.air_jumps.max(player.abilities.air_jumps);
+			},
+			Item::PowerUp(PowerUp::GroundPound) => {
+				player.abilities.ground_pound = true;
+				unlocked.ground_pound = true;
+			},
+			Item::PowerUp(PowerUp::Health(amount)) => {
+				health.current = (health.current+amount).min(health.max);
+			},
+		}
+	}
+}
+
+pub enum Lock {
+	// Opened by using up a key with this name.
+	Key(String),
+	// Opened by having collected this many coins, they aren't used up.
+	Coins(u32),
+}
+
+// A solid door from the map that opens when the player touches it with what
+// its lock needs.
+pub struct Door {
+	// Name of the map object, scripts open doors by name.
+	pub name: String,
+	pub cube: Cube,
+	pub lock: Lock,
+	pub open: bool,
+	// Opened before the last checkpoint, these stay open after dying.
+	pub saved: bool,
+}
+
+impl Door {
+	// Doors with a "coins" property need coins, the others need the key named by "key".
+	pub fn from_object(obj: &MapObject, textures: &mut crate::asset::Textures) -> Door {
+		let lock = match obj.properties.get("coins").and_then(|c| c.parse().ok()) {
+			Some(coins) => Lock::Coins(coins),
+			None => Lock::Key(obj.properties.get("key").cloned().unwrap_or(String::from(DEFAULT_KEY))),
+		};
+		let texture = obj.properties.get("texture").map(|t| t.as_str()).unwrap_or("assets/sprites/door.png");
+		Door {
+			name: obj.name.clone(),
+			cube: Cube::new(obj.size.x, obj.size.y, obj.pos.x, obj.pos.y, texture, textures),
+			lock,
+			open: false,
+			saved: false,
+		}
+	}
+	pub fn update(&mut self, transform: &Transform, inventory: &mut Inventory) {
+		let reach = Vec2::new(self.cube.size.x+2.*DOOR_REACH, self.cube.size.y+2.*DOOR_REACH);
+		if self.open || !Rect::new(self.cube.pos, reach).overlaps(&transform.pos, &transform.size) {
+			return
+		}
+		self.open = match &self.lock {
+			Lock::Coins(coins) => inventory.coins >= *coins,
+			Lock::Key(key) => match inventory.keys.get_mut(key) {
+				Some(count) if *count > 0 => {
+					*count -= 1;
+					true
+				},
+				_ => false,
+			},
+		};
+	}
+	pub fn render(&mut self, camera: &crate::Camera, batch: &mut crate::render::Batch) {
+		if !self.open {
+			self.cube.render(camera, batch);
+		}
+	}
+}
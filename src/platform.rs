@@ -0,0 +1,148 @@
+use crate::collision::Cube;
+use crate::loader::{MapObject, Shape};
+use crate::vector::Vec2;
+use crate::METER_IN_PIXELS;
+
+pub enum PathMode {
+	// Move to the end of the path and stop there.
+	Once,
+	// Move back and forth between the ends of the path.
+	PingPong,
+	// Move from the end of the path back to the start, and around again.
+	Loop,
+	// Move around an ellipse.
+	Circle { center: Vec2, radius: Vec2 },
+}
+
+// A solid platform moving along a path from the map, turned by its "angle"
+// property and turning by its "spin" property, both in degrees. Players standing on it are carried along, and players in its
+// way are pushed.
+pub struct Platform {
+	pub cube: Cube,
+	points: Vec<Vec2>,
+	mode: PathMode,
+	// Speed along the path, in meters per second.
+	speed: f64,
+	// Distance travelled along the path in pixels, or the angle for circles.
+	distance: f64,
+	direction: f64,
+	// How far the platform moved during the last update, in pixels.
+	pub delta: Vec2,
+	// Turning speed in radians per second, and how far it turned during the last update.
+	spin: f64,
+	pub turn: f64,
+}
+
+impl Platform {
+	// Polylines are followed once, back and forth or looped depending on the "mode"
+	// property, polygons are looped and ellipses are circled.
+	pub fn from_object(obj: &MapObject, textures: &mut crate::asset::Textures) -> Option<Platform> {
+		let property = |name: &str, default: f64| obj.properties.get(name).and_then(|v| v.parse().ok()).unwrap_or(default);
+		let (points, mode) = match &obj.shape {
+			Shape::Polyline(points) => {
+				let mode = match obj.properties.get("mode").map(|m| m.as_str()) {
+					Some("once") => PathMode::Once,
+					Some("loop") => PathMode::Loop,
+					_ => PathMode::PingPong,
+				};
+				(points.clone(), mode)
+			},
+			Shape::Polygon(points) => (points.clone(), PathMode::Loop),
+			Shape::Ellipse => (Vec::new(), PathMode::Circle {
+				center: obj.pos,
+				radius: Vec2::new(obj.size.x/2., obj.size.y/2.),
+			}),
+			Shape::Rectangle => return None,
+		};
+		let is_circle = matches!(mode, PathMode::Circle { .. });
+		if points.len() < 2 && !is_circle {
+			return None
+		}
+
+		let size = Vec2::new(property("width", 3.)*METER_IN_PIXELS, property("height", 1.)*METER_IN_PIXELS);
+		let texture = obj.properties.get("texture").map(|t| t.as_str()).unwrap_or("assets/sprites/brick.png");
+		let cube = Cube::new(size.x, size.y, 0., 0., texture, textures);
+		let mut platform = Platform {
+			cube,
+			points,
+			mode,
+			speed: property("speed", 2.),
+			distance: property("start", 0.),
+			direction: 1.,
+			delta: Vec2::new(0., 0.),
+			spin: property("spin", 0.).to_radians(),
+			turn: 0.,
+		};
+		platform.cube.pos = platform.position();
+		platform.cube.rotation = property("angle", 0.).to_radians();
+		Some(platform)
+	}
+	// Length of the path in pixels, including the way back to the start when looping.
+	fn length(&self) -> f64 {
+		let mut length = 0.;
+		for i in 1..self.points.len() {
+			length += distance(&self.points[i-1], &self.points[i]);
+		}
+		if let PathMode::Loop = self.mode {
+			length += distance(&self.points[self.points.len()-1], &self.points[0]);
+		}
+		length
+	}
+	fn position(&self) -> Vec2 {
+		if let PathMode::Circle { center, radius } = &self.mode {
+			return Vec2::new(center.x+radius.x*self.distance.cos(), center.y+radius.y*self.distance.sin())
+		}
+		let mut left = self.distance;
+		let count = self.points.len();
+		let segments = if let PathMode::Loop = self.mode { count } else { count-1 };
+		for i in 0..segments {
+			let (a, b) = (&self.points[i], &self.points[(i+1)%count]);
+			let length = distance(a, b);
+			if left <= length && length > 0. {
+				let t = left/length;
+				return Vec2::new(a.x+(b.x-a.x)*t, a.y+(b.y-a.y)*t)
+			}
+			left -= length;
+		}
+		self.points[if let PathMode::Loop = self.mode { 0 } else { count-1 }]
+	}
+	pub fn update(&mut self, dt: f64) {
+		let step = self.speed*METER_IN_PIXELS*dt;
+		match &self.mode {
+			PathMode::Circle { radius, .. } => {
+				// Move along the circumference at the given speed.
+				self.distance += step/((radius.x+radius.y)/2.).max(1.);
+			},
+			PathMode::Once => {
+				self.distance = (self.distance+step).min(self.length());
+			},
+			PathMode::Loop => {
+				let length = self.length();
+				self.distance = (self.distance+step)%length.max(1.);
+			},
+			PathMode::PingPong => {
+				let length = self.length();
+				self.distance += step*self.direction;
+				if self.distance >= length {
+					self.distance = length-(self.distance-length);
+					self.direction = -1.;
+				}else if self.distance <= 0. {
+					self.distance = -self.distance;
+					self.direction = 1.;
+				}
+			},
+		}
+		let pos = self.position();
+		self.delta = Vec2::new(pos.x-self.cube.pos.x, pos.y-self.cube.pos.y);
+		self.cube.pos = pos;
+		self.turn = self.spin*dt;
+		self.cube.rotation = (self.cube.rotation+self.turn)%(2.*std::f64::consts::PI);
+	}
+	pub fn render(&mut self, camera: &crate::Camera, batch: &mut crate::render::Batch) {
+		self.cube.render(camera, batch);
+	}
+}
+
+fn distance(a: &Vec2, b: &Vec2) -> f64 {
+	((b.x-a.x)*(b.x-a.x)+(b.y-a.y)*(b.y-a.y)).sqrt()
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use serde::Deserialize;
+
+use crate::animator::{Animator, AnimatorDef};
+use crate::ecs::{Animation, Collider, Entity, Scene, Sprite, Transform};
+use crate::health::Health;
+use crate::loader::MapObject;
+use crate::mob::{Mob, Player};
+use crate::projectile;
+use crate::vector::Vec2;
+
+pub const PREFAB_DIR: &str = "assets/prefabs";
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+	// Moved by the keys.
+	Player,
+	// Moved by the AI named by the "ai" property.
+	Mob,
+	// Doesn't move by itself.
+	Prop,
+}
+
+// The projectile layer of the collider.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Layer {
+	None,
+	Player,
+	Mob,
+}
+
+// Everything needed to spawn an entity, read from "<name>.toml" in the prefab
+// directory. Map objects spawning a prefab can override its properties with
+// their own, and use their own size.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Prefab {
+	pub kind: Kind,
+	// Width and height in pixels.
+	pub size: [f64; 2],
+	pub sprite: Option<String>,
+	// Path of the JSON data of an Aseprite sprite sheet, animated entities show
+	// its clips instead of the sprite.
+	pub sheet: Option<String>,
+	// States of the animation and the transitions between them, entities
+	// without one loop the first clip of their sheet.
+	pub animator: Option<AnimatorDef>,
+	#[serde(skip)]
+	compiled: Option<Rc<Animator>>,
+	// Path of the movement profile of players.
+	pub movement: Option<String>,
+	pub layer: Layer,
+	pub health: i32,
+	// Read the same way as map object properties, ex. "ai" and "speed" of mobs.
+	pub properties: HashMap<String, toml::Value>,
+}
+
+impl Default for Prefab {
+	fn default() -> Prefab {
+		Prefab {
+			kind: Kind::Prop,
+			size: [32., 32.],
+			sprite: None,
+			sheet: None,
+			animator: None,
+			compiled: None,
+			movement: None,
+			layer: Layer::None,
+			health: 1,
+			properties: HashMap::new(),
+		}
+	}
+}
+
+impl Prefab {
+	fn compile(mut self) -> Result<Prefab, String> {
+		if let Some(def) = &self.animator {
+			self.compiled = Some(Rc::new(Animator::new(def)?));
+		}
+		Ok(self)
+	}
+	// Spawn the prefab centered on pos, the overrides replace its properties.
+	pub fn spawn(&self, name: &str, scene: &mut Scene, pos: Vec2, size: Option<Vec2>, overrides: &HashMap<String, String>, textures: &mut crate::asset::Textures) -> Entity {
+		let mut properties: HashMap<String, String> = self.properties.iter().map(|(name, value)| {
+			let value = match value {
+				toml::Value::String(text) => text.clone(),
+				value => value.to_string(),
+			};
+			(name.clone(), value)
+		}).collect();
+		properties.extend(overrides.iter().map(|(name, value)| (name.clone(), value.clone())));
+		let property = |name: &str, default: f64| properties.get(name).and_then(|v| v.parse().ok()).unwrap_or(default);
+
+		let mut transform = Transform::new(pos, size.unwrap_or(Vec2::new(self.size[0], self.size[1])));
+		transform.facing = if property("direction", 1.) < 0. { -1. } else { 1. };
+		let layer = match self.layer {
+			Layer::None => 0,
+			Layer::Player => projectile::LAYER_PLAYER,
+			Layer::Mob => projectile::LAYER_MOB,
+		};
+
+		let entity = scene.spawn();
+		scene.transforms.insert(entity, transform);
+		scene.velocities.insert(entity, Vec2::new(0., 0.));
+		scene.colliders.insert(entity, Collider { layer, on_ground: false });
+		scene.names.insert(entity, String::from(name));
+		scene.healths.insert(entity, Health::new(property("health", self.health as f64) as i32));
+		if let Some(sprite) = properties.get("texture").or(self.sprite.as_ref()) {
+			scene.sprites.insert(entity, Sprite {
+				texture: Some(textures.load(sprite)),
+				color: [1.0, 1.0, 1.0, 1.0],
+			});
+		}
+		if let Some(sheet) = self.sheet.as_ref().and_then(|path| textures.load_sheet(path)) {
+			let animator = self.compiled.clone().unwrap_or_else(|| Rc::new(Animator::single()));
+			scene.animations.insert(entity, Animation::new(sheet, animator));
+		}
+		match self.kind {
+			Kind::Player => scene.players.insert(entity, Player::new(self.movement.clone())),
+			Kind::Mob => {
+				scene.mobs.insert(entity, Mob::from_properties(&properties, pos));
+				scene.contact_damages.insert(entity, property("damage", 1.) as i32);
+			},
+			Kind::Prop => (),
+		}
+		entity
+	}
+}
+
+// Prefabs by name, each file is only read the first time it is used.
+pub struct Prefabs {
+	dir: String,
+	loaded: HashMap<String, Option<Prefab>>,
+}
+
+impl Prefabs {
+	pub fn new(dir: &str) -> Prefabs {
+		Prefabs {
+			dir: String::from(dir),
+			loaded: HashMap::new(),
+		}
+	}
+	pub fn get(&mut self, name: &str) -> Option<&Prefab> {
+		let dir = &self.dir;
+		self.loaded.entry(String::from(name)).or_insert_with(|| {
+			let path = format!("{}/{}.toml", dir, name);
+			match std::fs::read_to_string(&path) {
+				Ok(text) => match toml::from_str(&text).map_err(|e| e.to_string()).and_then(Prefab::compile) {
+					Ok(prefab) => Some(prefab),
+					Err(e) => {
+						println!("Invalid prefab {:?}: {}", path, e);
+						None
+					},
+				},
+				Err(e) => {
+					println!("Could not read prefab {:?}: {}", path, e);
+					None
+				},
+			}
+		}).as_ref()
+	}
+	pub fn spawn(&mut self, name: &str, scene: &mut Scene, pos: Vec2, textures: &mut crate::asset::Textures) -> Option<Entity> {
+		let prefab = self.get(name)?;
+		Some(prefab.spawn(name, scene, pos, None, &HashMap::new(), textures))
+	}
+	// Spawn the prefab named by the "prefab" property of a map object, or the
+	// default one. Objects keep their own size unless they are points.
+	pub fn spawn_object(&mut self, obj: &MapObject, default: &str, scene: &mut Scene, textures: &mut crate::asset::Textures) -> Option<Entity> {
+		let name = obj.properties.get("prefab").map(|p| p.as_str()).unwrap_or(default);
+		let prefab = self.get(name)?;
+		let size = if obj.size.x > 0. && obj.size.y > 0. { Some(obj.size) } else { None };
+		let name = if obj.name.is_empty() { name } else { &obj.name };
+		Some(prefab.spawn(name, scene, obj.pos, size, &obj.properties, textures))
+	}
+}
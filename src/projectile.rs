@@ -0,0 +1,124 @@
+use crate::collision::{Cube, Rect};
+use crate::event::GameEvent;
+use crate::health::Damage;
+use crate::ecs::{Entity, Scene};
+use crate::physics;
+use crate::vector::Vec2;
+
+// Layers a projectile can hit, combined into a mask.
+pub const LAYER_TERRAIN: u8 = 1;
+pub const LAYER_PLAYER: u8 = 1 << 1;
+pub const LAYER_MOB: u8 = 1 << 2;
+
+// Space for this many projectiles is made up front, more are only allocated
+// when that many are flying at once.
+const POOL_SIZE: usize = 64;
+// Speed things are knocked back with by projectiles, in meters per second.
+const KNOCKBACK: f64 = 2.;
+
+// What was hit by a projectile.
+pub enum Target {
+	Terrain,
+	Entity(Entity),
+}
+
+// Everything needed to fire a projectile.
+pub struct Shot {
+	pub pos: Vec2,
+	// In meters per second.
+	pub velocity: Vec2,
+	// Seconds before the projectile disappears.
+	pub lifetime: f64,
+	// Whether the projectile falls with the worlds gravity.
+	pub gravity: bool,
+	pub damage: i32,
+	// The layers the projectile can hit.
+	pub mask: u8,
+	// Width and height in pixels.
+	pub size: f64,
+	pub color: [f32; 4],
+}
+
+struct Projectile {
+	shot: Shot,
+	alive: bool,
+}
+
+// All projectiles in the level. Dead projectiles are kept and reused by the
+// next shot.
+pub struct Projectiles {
+	pool: Vec<Projectile>,
+}
+
+impl Projectiles {
+	pub fn new() -> Projectiles {
+		Projectiles {
+			pool: Vec::with_capacity(POOL_SIZE),
+		}
+	}
+	pub fn spawn(&mut self, shot: Shot) {
+		match self.pool.iter_mut().find(|p| !p.alive) {
+			Some(projectile) => {
+				projectile.shot = shot;
+				projectile.alive = true;
+			},
+			None => self.pool.push(Projectile { shot, alive: true }),
+		}
+	}
+	pub fn clear(&mut self) {
+		for projectile in &mut self.pool {
+			projectile.alive = false;
+		}
+	}
+	// Moves the projectiles and sends a Hit event for everything they hit, the
+	// projectile is gone after the first hit.
+	pub fn update(&mut self, world: &crate::World, collidables: &[Cube], scene: &Scene, events: &mut Vec<GameEvent>, dt: f64) {
+		for projectile in self.pool.iter_mut().filter(|p| p.alive) {
+			let shot = &mut projectile.shot;
+			shot.lifetime -= dt;
+			if shot.lifetime <= 0. {
+				projectile.alive = false;
+				continue
+			}
+			if shot.gravity {
+				shot.velocity.y += world.grav_const*dt;
+			}
+			physics::move_body(&mut shot.pos, &shot.velocity, dt);
+
+			let size = Vec2::new(shot.size, shot.size);
+			// Entities are hit when their collider is on one of the layers of the mask.
+			let target = scene.colliders.iter().filter(|(_, c)| c.layer & shot.mask != 0).find(|(entity, _)| {
+				scene.transforms.get(*entity).map(|t| Rect::new(t.pos, t.size).overlaps(&shot.pos, &size)).unwrap_or(false)
+			}).map(|(entity, _)| Target::Entity(entity));
+			let target = target.or_else(|| if shot.mask & LAYER_TERRAIN != 0 && physics::solid_at(&shot.pos, collidables, world) {
+				Some(Target::Terrain)
+			}else {
+				None
+			});
+
+			if let Some(target) = target {
+				let away = if shot.velocity.x < 0. { -1. } else { 1. };
+				events.push(GameEvent::Hit {
+					pos: shot.pos,
+					target,
+					damage: Damage { amount: shot.damage, knockback: Vec2::new(away*KNOCKBACK, -KNOCKBACK) },
+				});
+				projectile.alive = false;
+			}
+		}
+	}
+	pub fn render(&self, camera: &crate::Camera, renderer: &mut dyn crate::render::Renderer) {
+		let transform = crate::render::view(camera);
+		for shot in self.pool.iter().filter(|p| p.alive).map(|p| &p.shot) {
+			let (x, y, size) = (shot.pos.x, shot.pos.y, shot.size);
+			renderer.rectangle(shot.color, [x-size/2., y-size/2., size, size], transform);
+		}
+	}
+}
+
+// Velocity to fire at a target with, in meters per second.
+pub fn aim(from: &Vec2, to: &Vec2, speed: f64) -> Vec2 {
+	let (dx, dy) = (to.x-from.x, to.y-from.y);
+	let length = (dx*dx+dy*dy).sqrt().max(1.);
+	Vec2::new(dx/length*speed, dy/length*speed)
+}
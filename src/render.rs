@@ -1,42 +1,238 @@
-use crate::App;
-use piston::RenderArgs;
-use graphics::{rectangle,Transformed};
+use graphics::Transformed;
+use graphics::types::Matrix2d;
 use piston_window::PistonWindow;
 use piston_window::G2dTexture as Texture;
+use crate::asset::{TextureHandle, Textures};
+use crate::ecs::Transform;
+use crate::sheet::{Frame, SpriteSheet};
 
-pub struct PlayerAnimation {
-    pub stand: Texture,
-    pub walk1: Texture,
-    pub walk2: Texture,
-    pub walk3: Texture,
-    pub jump: Texture,
-    pub land: Texture,
-}
-
-impl PlayerAnimation {
-    pub fn new(mut window: &mut PistonWindow, texture_vector: &Vec<&str>) -> PlayerAnimation {
-        let stand = create_texture(&mut window, texture_vector[0]);
-        let walk1 = create_texture(&mut window, texture_vector[1]);
-        let walk2 = create_texture(&mut window, texture_vector[2]);
-        let walk3 = create_texture(&mut window, texture_vector[3]);
-        let jump = create_texture(&mut window, texture_vector[4]);
-        let land = create_texture(&mut window, texture_vector[5]);
-        PlayerAnimation {
-            stand,
-            walk1,
-            walk2,
-            walk3,
-            jump,
-            land,
+// Vertices handed to the backend at a time, whole sprites of two triangles.
+const CHUNK: usize = graphics::BACK_END_MAX_VERTEX_COUNT/6*6;
+
+// Where frames are drawn, a window or an image. Positions are given in pixels
+// of the frame once the transform is applied, colors are blended by alpha.
+pub trait Renderer {
+    // Bring the atlas pages up to date with the textures.
+    fn sync(&mut self, textures: &Textures);
+    fn clear(&mut self, color: [f32; 4]);
+    fn rectangle(&mut self, color: [f32; 4], rect: [f64; 4], transform: Matrix2d);
+    // Triangles of three vertices each, textured from an atlas page and tinted
+    // with the color.
+    fn triangles(&mut self, page: usize, color: [f32; 4], xy: &[[f64; 2]], uv: &[[f32; 2]], transform: Matrix2d);
+    // Draw anything still waiting, at the end of a frame.
+    fn finish(&mut self) {}
+}
+
+// Maps world pixels to window pixels, the camera position is the top left
+// corner of the view once zoomed around the center of the window.
+pub fn view(camera: &crate::Camera) -> Matrix2d {
+    let scale = camera.zoom;
+    graphics::math::identity()
+        .trans(camera.w/2.*(1.-scale)-camera.position.x, camera.h/2.*(1.-scale)-camera.position.y)
+        .scale(scale, scale)
+}
+
+// The part of the world the camera sees, as left, top, right and bottom edges
+// in world pixels.
+pub fn visible_area(camera: &crate::Camera) -> [f64; 4] {
+    let scale = camera.zoom;
+    let left = (camera.position.x-camera.w/2.*(1.-scale))/scale;
+    let top = (camera.position.y-camera.h/2.*(1.-scale))/scale;
+    [left, top, left+camera.w/scale, top+camera.h/scale]
+}
+
+// The atlas pages on the GPU, uploaded again when they change. Kept between
+// frames, unlike the renderer drawing with it.
+pub struct Gpu {
+    context: piston_window::G2dTextureContext,
+    pages: Vec<(u32, Texture)>,
+}
+
+impl Gpu {
+    pub fn new(window: &mut PistonWindow) -> Gpu {
+        Gpu {
+            context: window.create_texture_context(),
+            pages: Vec::new(),
+        }
+    }
+    pub fn sync(&mut self, textures: &Textures) {
+        for (i, page) in textures.pages().iter().enumerate() {
+            if self.pages.get(i).map(|p| p.0) == Some(page.version) {
+                continue
+            }
+            let texture = Texture::from_image(&mut self.context, &page.image, &piston_window::TextureSettings::new())
+                .expect("Could not upload a texture atlas page");
+            if i < self.pages.len() {
+                self.pages[i] = (page.version, texture);
+            }else {
+                self.pages.push((page.version, texture));
+            }
+        }
+    }
+}
+
+// Draws to the window with OpenGL, during a render event.
+pub struct Piston<'a> {
+    window: &'a mut PistonWindow,
+    event: &'a piston::Event,
+    gpu: &'a mut Gpu,
+    // Rectangles are drawn together when something else is drawn, text is
+    // made of a lot of them.
+    rectangles: Vec<([f32; 4], [f64; 4], Matrix2d)>,
+}
+
+impl<'a> Piston<'a> {
+    pub fn new(window: &'a mut PistonWindow, event: &'a piston::Event, gpu: &'a mut Gpu) -> Piston<'a> {
+        Piston {
+            window,
+            event,
+            gpu,
+            rectangles: Vec::new(),
+        }
+    }
+    fn draw_rectangles(&mut self) {
+        if self.rectangles.is_empty() {
+            return
+        }
+        let rectangles = &self.rectangles;
+        self.window.draw_2d(self.event, |c, g, _| {
+            for (color, rect, transform) in rectangles {
+                graphics::rectangle(*color, *rect, c.transform.append_transform(*transform), g);
+            }
+        });
+        self.rectangles.clear();
+    }
+}
+
+impl Renderer for Piston<'_> {
+    fn sync(&mut self, textures: &Textures) {
+        self.gpu.sync(textures);
+    }
+    fn clear(&mut self, color: [f32; 4]) {
+        self.rectangles.clear();
+        self.window.draw_2d(self.event, |_, g, _| graphics::clear(color, g));
+    }
+    fn rectangle(&mut self, color: [f32; 4], rect: [f64; 4], transform: Matrix2d) {
+        self.rectangles.push((color, rect, transform));
+    }
+    fn triangles(&mut self, page: usize, color: [f32; 4], xy: &[[f64; 2]], uv: &[[f32; 2]], transform: Matrix2d) {
+        self.draw_rectangles();
+        let texture = &self.gpu.pages[page].1;
+        self.window.draw_2d(self.event, |c, g, _| {
+            use graphics::Graphics;
+            use graphics::triangulation::{tx, ty};
+            let transform = c.transform.append_transform(transform);
+            let mut buffer = [[0f32; 2]; CHUNK];
+            g.tri_list_uv(&c.draw_state, &color, texture, |f| {
+                for (xy, uv) in xy.chunks(CHUNK).zip(uv.chunks(CHUNK)) {
+                    for (vertex, p) in buffer.iter_mut().zip(xy) {
+                        *vertex = [tx(transform, p[0], p[1]), ty(transform, p[0], p[1])];
+                    }
+                    f(&buffer[..xy.len()], uv);
+                }
+            });
+        });
+    }
+    fn finish(&mut self) {
+        self.draw_rectangles();
+    }
+}
+
+// Sprites with the same atlas page and color, as triangles in world pixels.
+struct Group {
+    page: usize,
+    color: [f32; 4],
+    xy: Vec<[f64; 2]>,
+    uv: Vec<[f32; 2]>,
+}
+
+// Sprites collected to be drawn together, with a single draw for every atlas
+// page and color. Sprites on different pages don't keep their order.
+pub struct Batch {
+    groups: Vec<Group>,
+    // Counted since the last reset, for benchmarks.
+    pub sprites: usize,
+    pub draws: usize,
+}
+
+impl Batch {
+    pub fn new() -> Batch {
+        Batch {
+            groups: Vec::new(),
+            sprites: 0,
+            draws: 0,
+        }
+    }
+    // Add the part src of a texture, in pixels of the texture, over the area dest
+    // in world pixels. A negative width mirrors it.
+    pub fn add(&mut self, texture: &TextureHandle, src: Option<[f64; 4]>, dest: [f64; 4], color: [f32; 4]) {
+        let region = texture.region();
+        let [u0, v0, u1, v1] = region.uv(src.unwrap_or([0., 0., region.width(), region.height()]));
+        let (x0, y0, x1, y1) = (dest[0], dest[1], dest[0]+dest[2], dest[1]+dest[3]);
+        let group = self.group(region.page, color);
+        group.xy.extend_from_slice(&[[x0, y0], [x1, y0], [x0, y1], [x1, y0], [x1, y1], [x0, y1]]);
+        group.uv.extend_from_slice(&[[u0, v0], [u1, v0], [u0, v1], [u1, v0], [u1, v1], [u0, v1]]);
+        self.sprites += 1;
+    }
+    // Add a whole texture over the area dest turned by the angle in radians
+    // around its center.
+    pub fn add_rotated(&mut self, texture: &TextureHandle, dest: [f64; 4], angle: f64, color: [f32; 4]) {
+        let region = texture.region();
+        let [u0, v0, u1, v1] = region.uv([0., 0., region.width(), region.height()]);
+        let (cx, cy) = (dest[0]+dest[2]/2., dest[1]+dest[3]/2.);
+        let (sin, cos) = angle.sin_cos();
+        let corner = |x: f64, y: f64| [cx+x*cos-y*sin, cy+x*sin+y*cos];
+        let (w, h) = (dest[2]/2., dest[3]/2.);
+        let (a, b, c, d) = (corner(-w, -h), corner(w, -h), corner(w, h), corner(-w, h));
+        let group = self.group(region.page, color);
+        group.xy.extend_from_slice(&[a, b, d, b, c, d]);
+        group.uv.extend_from_slice(&[[u0, v0], [u1, v0], [u0, v1], [u1, v0], [u1, v1], [u0, v1]]);
+        self.sprites += 1;
+    }
+    // Add everything in another batch, ex. one that is built once and kept.
+    pub fn extend(&mut self, other: &Batch) {
+        for other in &other.groups {
+            let group = self.group(other.page, other.color);
+            group.xy.extend_from_slice(&other.xy);
+            group.uv.extend_from_slice(&other.uv);
+            self.sprites += other.xy.len()/6;
         }
     }
+    fn group(&mut self, page: usize, color: [f32; 4]) -> &mut Group {
+        let i = match self.groups.iter().position(|g| g.page == page && g.color == color) {
+            Some(i) => i,
+            None => {
+                self.groups.push(Group { page, color, xy: Vec::new(), uv: Vec::new() });
+                self.groups.len()-1
+            },
+        };
+        &mut self.groups[i]
+    }
+    // Draw everything added since the last draw as seen by the camera.
+    pub fn draw(&mut self, renderer: &mut dyn Renderer, camera: &crate::Camera) {
+        let transform = view(camera);
+        for group in &self.groups {
+            renderer.triangles(group.page, group.color, &group.xy, &group.uv, transform);
+        }
+        self.draws += self.groups.len();
+        self.groups.clear();
+    }
 }
 
-pub fn create_texture(window: &mut PistonWindow, texture_path: &str) -> Texture {
-    Texture::from_path(
-        &mut window.create_texture_context(),
-        &texture_path,
-        piston_window::Flip::None,
-        &piston_window::TextureSettings::new()
-    ).unwrap()
-}
\ No newline at end of file
+// Draw a texture over the area of the transform, mirrored when facing left.
+pub fn draw_texture(batch: &mut Batch, texture: &TextureHandle, color: [f32; 4], transform: &Transform) {
+    let (x, y) = (transform.pos.x, transform.pos.y);
+    let (w, h) = (transform.size.x, transform.size.y);
+    let look_dir = transform.facing;
+    batch.add(texture, None, [x-(w*look_dir)/2., y-h/2., w*look_dir, h], color);
+}
+
+// Draw a frame of a sprite sheet scaled to the area of the transform, with the
+// pivot of the frame on the position. Mirrored around the pivot when facing left.
+pub fn draw_frame(batch: &mut Batch, sheet: &SpriteSheet, frame: &Frame, color: [f32; 4], transform: &Transform) {
+    let (sx, sy) = (transform.size.x/frame.rect[2], transform.size.y/frame.rect[3]);
+    let look_dir = transform.facing;
+    let x = transform.pos.x-frame.pivot[0]*sx*look_dir;
+    let y = transform.pos.y-frame.pivot[1]*sy;
+    batch.add(&sheet.texture, Some(frame.rect), [x, y, frame.rect[2]*sx*look_dir, frame.rect[3]*sy], color);
+}
@@ -0,0 +1,266 @@
+use std::fs::File;
+use std::io::{self, Read, Write, BufReader, BufWriter};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::Keys;
+use crate::ability::Abilities;
+use crate::ecs::Scene;
+use crate::movement::MovementProfile;
+
+const MAGIC: &[u8; 4] = b"PTGR";
+// Changed whenever the file or the key bits change, 2 added dash, fire and
+// ground pound, 3 added the abilities and movement profiles.
+const VERSION: u8 = 3;
+// How many ticks pass between each player state checksum.
+pub const CHECKSUM_INTERVAL: u32 = 60;
+pub const DEFAULT_PATH: &str = "replays/latest.replay";
+
+pub enum Mode {
+	Record(Recorder),
+	Play(Playback),
+}
+
+pub struct Header {
+	pub map_path: String,
+	pub dt: f64,
+	pub checksum_interval: u32,
+	// Abilities unlocked in the save the recording started from.
+	pub abilities: Abilities,
+}
+
+// Records the key state of every tick and a checksum of the players every
+// CHECKSUM_INTERVAL ticks. Inputs are run length encoded since keys are
+// usually held for many ticks in a row. The saved abilities and movement
+// profile files are recorded too, so a replay plays the same on any machine
+// and after the files are tuned.
+pub struct Recorder {
+	pub header: Header,
+	path: String,
+	inputs: Vec<(u8, u16)>,
+	checksums: Vec<u64>,
+	// Movement profiles as read from their file, with the first tick they were used on.
+	movement: Vec<(u32, MovementProfile)>,
+	tick: u32,
+}
+
+impl Recorder {
+	pub fn new(path: &str, map_path: &str, dt: f64, abilities: &Abilities) -> Recorder {
+		Recorder {
+			header: Header {
+				map_path: String::from(map_path),
+				dt,
+				checksum_interval: CHECKSUM_INTERVAL,
+				abilities: abilities.clone(),
+			},
+			path: String::from(path),
+			inputs: Vec::new(),
+			checksums: Vec::new(),
+			movement: Vec::new(),
+			tick: 0,
+		}
+	}
+	// Must be called between ticks whenever a movement profile is loaded.
+	pub fn record_movement(&mut self, profile: &MovementProfile) {
+		if self.movement.last().map(|(_, last)| last != profile).unwrap_or(true) {
+			self.movement.push((self.tick, *profile));
+		}
+	}
+	// Must be called once per tick, before the simulation is updated.
+	pub fn record(&mut self, keys: &Keys) {
+		let bits = keys.to_bits();
+		match self.inputs.last_mut() {
+			Some((last, count)) if *last == bits && *count < u16::MAX => *count += 1,
+			_ => self.inputs.push((bits, 1)),
+		}
+	}
+	// Must be called once per tick, after the simulation is updated.
+	pub fn end_tick(&mut self, scene: &Scene) {
+		self.tick += 1;
+		if self.tick.is_multiple_of(self.header.checksum_interval) {
+			self.checksums.push(checksum(scene));
+		}
+	}
+	pub fn save(&self) -> io::Result<()> {
+		if let Some(dir) = std::path::Path::new(&self.path).parent() {
+			std::fs::create_dir_all(dir)?;
+		}
+		let mut file = BufWriter::new(File::create(&self.path)?);
+		file.write_all(MAGIC)?;
+		file.write_all(&[VERSION])?;
+		let map_path = self.header.map_path.as_bytes();
+		file.write_all(&(map_path.len() as u16).to_le_bytes())?;
+		file.write_all(map_path)?;
+		file.write_all(&self.header.dt.to_bits().to_le_bytes())?;
+		file.write_all(&self.header.checksum_interval.to_le_bytes())?;
+		write_toml(&mut file, &self.header.abilities)?;
+		file.write_all(&(self.inputs.len() as u32).to_le_bytes())?;
+		for (bits, count) in &self.inputs {
+			file.write_all(&[*bits])?;
+			file.write_all(&count.to_le_bytes())?;
+		}
+		file.write_all(&(self.checksums.len() as u32).to_le_bytes())?;
+		for sum in &self.checksums {
+			file.write_all(&sum.to_le_bytes())?;
+		}
+		file.write_all(&(self.movement.len() as u32).to_le_bytes())?;
+		for (tick, profile) in &self.movement {
+			file.write_all(&tick.to_le_bytes())?;
+			write_toml(&mut file, profile)?;
+		}
+		file.flush()
+	}
+}
+
+// Feeds a recorded replay back into the simulation and compares the player
+// state against the recorded checksums.
+pub struct Playback {
+	pub header: Header,
+	inputs: Vec<(u8, u16)>,
+	checksums: Vec<u64>,
+	movement: Vec<(u32, MovementProfile)>,
+	run: usize,
+	run_tick: u16,
+	tick: u32,
+	pub desync_tick: Option<u32>,
+}
+
+impl Playback {
+	pub fn load(path: &str) -> io::Result<Playback> {
+		let mut file = BufReader::new(File::open(path)?);
+		let mut magic = [0u8; 4];
+		file.read_exact(&mut magic)?;
+		if &magic != MAGIC || read_u8(&mut file)? != VERSION {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{:?} is not a valid replay file!", path)));
+		}
+		let mut map_path = vec![0u8; read_u16(&mut file)? as usize];
+		file.read_exact(&mut map_path)?;
+		let map_path = String::from_utf8(map_path)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+		let dt = f64::from_bits(read_u64(&mut file)?);
+		let checksum_interval = read_u32(&mut file)?;
+		if checksum_interval == 0 {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{:?} has no checksum interval!", path)));
+		}
+		let abilities = read_toml(&mut file)?;
+		let mut inputs = Vec::new();
+		for _ in 0..read_u32(&mut file)? {
+			inputs.push((read_u8(&mut file)?, read_u16(&mut file)?));
+		}
+		let mut checksums = Vec::new();
+		for _ in 0..read_u32(&mut file)? {
+			checksums.push(read_u64(&mut file)?);
+		}
+		let mut movement = Vec::new();
+		for _ in 0..read_u32(&mut file)? {
+			movement.push((read_u32(&mut file)?, read_toml(&mut file)?));
+		}
+		Ok(Playback {
+			header: Header {
+				map_path,
+				dt,
+				checksum_interval,
+				abilities,
+			},
+			inputs,
+			checksums,
+			movement,
+			run: 0,
+			run_tick: 0,
+			tick: 0,
+			desync_tick: None,
+		})
+	}
+	// Returns the key state for the next tick, or None when the replay is over.
+	pub fn next_keys(&mut self) -> Option<Keys> {
+		let (bits, count) = *self.inputs.get(self.run)?;
+		self.run_tick += 1;
+		if self.run_tick >= count {
+			self.run += 1;
+			self.run_tick = 0;
+		}
+		Some(Keys::from_bits(bits))
+	}
+	pub fn finished(&self) -> bool {
+		self.run >= self.inputs.len()
+	}
+	// The movement profile file as it was when the recording reached this tick.
+	pub fn movement(&self) -> Option<MovementProfile> {
+		self.movement.iter().take_while(|(tick, _)| *tick <= self.tick).last().map(|(_, profile)| *profile)
+	}
+	// Must be called once per tick, after the simulation is updated.
+	pub fn end_tick(&mut self, scene: &Scene) {
+		self.tick += 1;
+		if self.tick.is_multiple_of(self.header.checksum_interval) {
+			let index = (self.tick/self.header.checksum_interval) as usize - 1;
+			if let Some(expected) = self.checksums.get(index) {
+				if *expected != checksum(scene) && self.desync_tick.is_none() {
+					println!("\nReplay desynced at tick {}!", self.tick);
+					self.desync_tick = Some(self.tick);
+				}
+			}
+		}
+		if self.finished() && self.desync_tick.is_none() {
+			println!("\nReplay finished after {} ticks without desyncing.", self.tick);
+		}
+	}
+}
+
+// FNV-1a hash over the exact bits of every players position and velocity.
+pub fn checksum(scene: &Scene) -> u64 {
+	let mut hash: u64 = 0xcbf29ce484222325;
+	for player in scene.players() {
+		let (transform, velocity, collider) = match (scene.transforms.get(player), scene.velocities.get(player), scene.colliders.get(player)) {
+			(Some(t), Some(v), Some(c)) => (t, v, c),
+			_ => continue,
+		};
+		let values = [transform.pos.x, transform.pos.y, velocity.x, velocity.y];
+		for value in values.iter() {
+			for byte in value.to_bits().to_le_bytes().iter() {
+				hash ^= *byte as u64;
+				hash = hash.wrapping_mul(0x100000001b3);
+			}
+		}
+		hash ^= collider.on_ground as u64;
+		hash = hash.wrapping_mul(0x100000001b3);
+	}
+	hash
+}
+
+// Values with a serde form are stored as TOML text after its length.
+fn write_toml<W: Write, T: Serialize>(w: &mut W, value: &T) -> io::Result<()> {
+	let text = toml::to_string(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+	w.write_all(&(text.len() as u32).to_le_bytes())?;
+	w.write_all(text.as_bytes())
+}
+
+fn read_toml<R: Read, T: DeserializeOwned>(r: &mut R) -> io::Result<T> {
+	let mut text = vec![0u8; read_u32(r)? as usize];
+	r.read_exact(&mut text)?;
+	let text = String::from_utf8(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+	toml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn read_u8<R: Read>(r: &mut R) -> io::Result<u8> {
+	let mut buf = [0u8; 1];
+	r.read_exact(&mut buf)?;
+	Ok(buf[0])
+}
+
+pub fn read_u16<R: Read>(r: &mut R) -> io::Result<u16> {
+	let mut buf = [0u8; 2];
+	r.read_exact(&mut buf)?;
+	Ok(u16::from_le_bytes(buf))
+}
+
+pub fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+	let mut buf = [0u8; 4];
+	r.read_exact(&mut buf)?;
+	Ok(u32::from_le_bytes(buf))
+}
+
+pub fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+	let mut buf = [0u8; 8];
+	r.read_exact(&mut buf)?;
+	Ok(u64::from_le_bytes(buf))
+}
@@ -0,0 +1,36 @@
+use std::io;
+use serde::{Serialize, Deserialize};
+
+use crate::ability::Abilities;
+
+pub const SAVE_PATH: &str = "saves/save.toml";
+
+// Progress that is kept between sessions.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct SaveData {
+	pub abilities: Abilities,
+}
+
+impl SaveData {
+	// Load the save file, starting a new save if there is none.
+	pub fn load(path: &str) -> SaveData {
+		match std::fs::read_to_string(path) {
+			Ok(text) => match toml::from_str(&text) {
+				Ok(save) => save,
+				Err(e) => {
+					println!("Invalid save file {:?}: {}", path, e);
+					SaveData::default()
+				},
+			},
+			Err(_) => SaveData::default(),
+		}
+	}
+	pub fn save(&self, path: &str) -> io::Result<()> {
+		if let Some(dir) = std::path::Path::new(path).parent() {
+			std::fs::create_dir_all(dir)?;
+		}
+		let text = toml::to_string(self).map_err(io::Error::other)?;
+		std::fs::write(path, text)
+	}
+}
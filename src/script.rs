@@ -0,0 +1,301 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::SystemTime;
+use rhai::{Array, CallFnOptions, Dynamic, Engine, FuncArgs, Map, Scope, AST, INT};
+
+use crate::collision::Rect;
+use crate::ecs::{Entity, Scene};
+use crate::loader::MapObject;
+use crate::vector::Vec2;
+
+// Changes asked for by scripts, applied by the App after the scripts return.
+pub enum Command {
+	Spawn { prefab: String, pos: Vec2 },
+	Despawn(Entity),
+	Move { entity: Entity, pos: Vec2 },
+	Push { entity: Entity, velocity: Vec2 },
+	Hurt { entity: Entity, amount: i32 },
+	// Point to look at instead of following the player, None to follow again.
+	LookAt(Option<Vec2>),
+	Zoom(f64),
+	OpenDoor(String),
+}
+
+// An entity the way scripts see it.
+struct EntityInfo {
+	entity: Entity,
+	name: String,
+	pos: Vec2,
+	velocity: Vec2,
+	health: i32,
+}
+
+// What the script functions read from and write to, shared with the engine.
+struct Bridge {
+	entities: Vec<EntityInfo>,
+	keys: crate::Keys,
+	commands: Vec<Command>,
+}
+
+impl Bridge {
+	fn find(&self, id: INT) -> Option<&EntityInfo> {
+		let entity = Entity::from_bits(id as u64);
+		self.entities.iter().find(|e| e.entity == entity)
+	}
+}
+
+struct Script {
+	path: String,
+	ast: Option<AST>,
+	modified: Option<SystemTime>,
+	// Bound to "this" in the script functions, kept when the script is reloaded.
+	state: Dynamic,
+}
+
+// An area from the map, scripts are told when the player enters it.
+struct Trigger {
+	name: String,
+	area: Rect,
+	inside: bool,
+}
+
+// The Rhai scripts of the current map, named by the comma separated "script"
+// map property. Scripts define any of the functions init(), update(dt),
+// on_trigger(name), on_hit(id, damage), on_touch(player, mob), on_death() and
+// on_animation(id, event).
+pub struct Scripts {
+	engine: Engine,
+	bridge: Rc<RefCell<Bridge>>,
+	scripts: Vec<Script>,
+	triggers: Vec<Trigger>,
+}
+
+impl Scripts {
+	pub fn new() -> Scripts {
+		let bridge = Rc::new(RefCell::new(Bridge {
+			entities: Vec::new(),
+			keys: crate::Keys::new(),
+			commands: Vec::new(),
+		}));
+		let mut engine = Engine::new();
+		register(&mut engine, &bridge);
+		Scripts {
+			engine,
+			bridge,
+			scripts: Vec::new(),
+			triggers: Vec::new(),
+		}
+	}
+	pub fn clear(&mut self) {
+		self.scripts.clear();
+		self.triggers.clear();
+	}
+	pub fn add_trigger(&mut self, obj: &MapObject) {
+		self.triggers.push(Trigger { name: obj.name.clone(), area: Rect::new(obj.pos, obj.size), inside: false });
+	}
+	// Load the scripts named by the map properties and call their init().
+	pub fn start(&mut self, properties: &HashMap<String, String>, scene: &Scene, keys: &crate::Keys) {
+		let paths = properties.get("script").map(|s| s.as_str()).unwrap_or("");
+		for path in paths.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+			let mut script = Script {
+				path: String::from(path),
+				ast: None,
+				modified: None,
+				state: Dynamic::from(Map::new()),
+			};
+			compile(&self.engine, &mut script);
+			self.scripts.push(script);
+		}
+		self.sync(scene, keys);
+		self.call("init", ());
+	}
+	// Recompile the scripts that have changed since they were loaded.
+	pub fn reload_changed(&mut self) {
+		for script in &mut self.scripts {
+			if modified_time(&script.path) != script.modified {
+				compile(&self.engine, script);
+				println!("\nReloaded script {:?}", script.path);
+			}
+		}
+	}
+	// Tell the scripts about triggers entered by the first player, then call their update().
+	pub fn update(&mut self, scene: &Scene, keys: &crate::Keys, dt: f64) {
+		self.sync(scene, keys);
+		let player = scene.players().first().and_then(|p| scene.transforms.get(*p)).cloned();
+		let mut entered = Vec::new();
+		for trigger in &mut self.triggers {
+			let inside = player.map(|p| trigger.area.overlaps(&p.pos, &p.size)).unwrap_or(false);
+			if inside && !trigger.inside {
+				entered.push(trigger.name.clone());
+			}
+			trigger.inside = inside;
+		}
+		for name in entered {
+			self.call("on_trigger", (name,));
+		}
+		self.call("update", (dt,));
+	}
+	pub fn on_hit(&mut self, entity: Entity, damage: i32) {
+		self.call("on_hit", (entity.to_bits() as INT, damage as INT));
+	}
+	pub fn on_touch(&mut self, player: Entity, mob: Entity) {
+		self.call("on_touch", (player.to_bits() as INT, mob.to_bits() as INT));
+	}
+	pub fn on_death(&mut self) {
+		self.call("on_death", ());
+	}
+	pub fn on_animation(&mut self, entity: Entity, event: &str) {
+		self.call("on_animation", (entity.to_bits() as INT, String::from(event)));
+	}
+	pub fn take_commands(&mut self) -> Vec<Command> {
+		std::mem::take(&mut self.bridge.borrow_mut().commands)
+	}
+	// Update what the scripts see of the game.
+	fn sync(&mut self, scene: &Scene, keys: &crate::Keys) {
+		let mut bridge = self.bridge.borrow_mut();
+		bridge.keys = *keys;
+		bridge.entities = scene.transforms.iter().map(|(entity, transform)| EntityInfo {
+			entity,
+			name: scene.names.get(entity).cloned().unwrap_or_default(),
+			pos: transform.pos,
+			velocity: scene.velocities.get(entity).cloned().unwrap_or(Vec2::new(0., 0.)),
+			health: scene.healths.get(entity).map(|h| h.current).unwrap_or(0),
+		}).collect();
+	}
+	// Call a function in every script that defines it, errors are printed.
+	fn call<A: FuncArgs + Clone>(&mut self, name: &str, args: A) {
+		let engine = &self.engine;
+		for script in &mut self.scripts {
+			let ast = match &script.ast {
+				Some(ast) => ast,
+				None => continue,
+			};
+			if !ast.iter_functions().any(|f| f.name == name) {
+				continue
+			}
+			let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut script.state);
+			if let Err(e) = engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), ast, name, args.clone()) {
+				println!("\nError in {}() of script {:?}: {}", name, script.path, e);
+			}
+		}
+	}
+}
+
+fn compile(engine: &Engine, script: &mut Script) {
+	script.modified = modified_time(&script.path);
+	script.ast = match std::fs::read_to_string(&script.path) {
+		Ok(text) => match engine.compile(&text) {
+			Ok(ast) => Some(ast),
+			Err(e) => {
+				println!("Invalid script {:?}: {}", script.path, e);
+				None
+			},
+		},
+		Err(e) => {
+			println!("Could not read script {:?}: {}", script.path, e);
+			None
+		},
+	};
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+	std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// Scripts can use integers and floats for any number.
+fn number(value: &Dynamic) -> f64 {
+	value.as_float().or_else(|_| value.as_int().map(|i| i as f64)).unwrap_or(0.)
+}
+
+fn vector(v: Vec2) -> Dynamic {
+	let mut map = Map::new();
+	map.insert("x".into(), Dynamic::from(v.x));
+	map.insert("y".into(), Dynamic::from(v.y));
+	Dynamic::from(map)
+}
+
+// The functions scripts can call. Entities are referred to by id numbers,
+// positions are in pixels and velocities in meters per second.
+fn register(engine: &mut Engine, bridge: &Rc<RefCell<Bridge>>) {
+	let b = bridge.clone();
+	engine.register_fn("entities", move || -> Array {
+		b.borrow().entities.iter().map(|e| Dynamic::from(e.entity.to_bits() as INT)).collect()
+	});
+	// Entities spawned from map objects are named after the object, the others after their prefab.
+	let b = bridge.clone();
+	engine.register_fn("find", move |name: &str| -> Array {
+		b.borrow().entities.iter().filter(|e| e.name == name).map(|e| Dynamic::from(e.entity.to_bits() as INT)).collect()
+	});
+	let b = bridge.clone();
+	engine.register_fn("name", move |id: INT| -> String {
+		b.borrow().find(id).map(|e| e.name.clone()).unwrap_or_default()
+	});
+	let b = bridge.clone();
+	engine.register_fn("pos", move |id: INT| -> Dynamic {
+		b.borrow().find(id).map(|e| vector(e.pos)).unwrap_or(Dynamic::UNIT)
+	});
+	let b = bridge.clone();
+	engine.register_fn("velocity", move |id: INT| -> Dynamic {
+		b.borrow().find(id).map(|e| vector(e.velocity)).unwrap_or(Dynamic::UNIT)
+	});
+	let b = bridge.clone();
+	engine.register_fn("health", move |id: INT| -> INT {
+		b.borrow().find(id).map(|e| e.health as INT).unwrap_or(0)
+	});
+	let b = bridge.clone();
+	engine.register_fn("key", move |name: &str| -> bool {
+		let keys = b.borrow().keys;
+		match name {
+			"jump" | "space" => keys.space,
+			"left" | "a" => keys.a,
+			"right" | "d" => keys.d,
+			"down" | "s" => keys.s,
+			"up" | "w" => keys.w,
+			"dash" | "shift" => keys.shift,
+			"fire" => keys.fire,
+			"pound" => keys.pound,
+			_ => false,
+		}
+	});
+
+	let b = bridge.clone();
+	engine.register_fn("spawn", move |prefab: &str, x: Dynamic, y: Dynamic| {
+		let pos = Vec2::new(number(&x), number(&y));
+		b.borrow_mut().commands.push(Command::Spawn { prefab: String::from(prefab), pos });
+	});
+	let b = bridge.clone();
+	engine.register_fn("despawn", move |id: INT| {
+		b.borrow_mut().commands.push(Command::Despawn(Entity::from_bits(id as u64)));
+	});
+	let b = bridge.clone();
+	engine.register_fn("move_to", move |id: INT, x: Dynamic, y: Dynamic| {
+		let pos = Vec2::new(number(&x), number(&y));
+		b.borrow_mut().commands.push(Command::Move { entity: Entity::from_bits(id as u64), pos });
+	});
+	let b = bridge.clone();
+	engine.register_fn("push", move |id: INT, x: Dynamic, y: Dynamic| {
+		let velocity = Vec2::new(number(&x), number(&y));
+		b.borrow_mut().commands.push(Command::Push { entity: Entity::from_bits(id as u64), velocity });
+	});
+	let b = bridge.clone();
+	engine.register_fn("hurt", move |id: INT, amount: INT| {
+		b.borrow_mut().commands.push(Command::Hurt { entity: Entity::from_bits(id as u64), amount: amount as i32 });
+	});
+	let b = bridge.clone();
+	engine.register_fn("look_at", move |x: Dynamic, y: Dynamic| {
+		b.borrow_mut().commands.push(Command::LookAt(Some(Vec2::new(number(&x), number(&y)))));
+	});
+	let b = bridge.clone();
+	engine.register_fn("follow_player", move || {
+		b.borrow_mut().commands.push(Command::LookAt(None));
+	});
+	let b = bridge.clone();
+	engine.register_fn("zoom", move |zoom: Dynamic| {
+		b.borrow_mut().commands.push(Command::Zoom(number(&zoom)));
+	});
+	let b = bridge.clone();
+	engine.register_fn("open_door", move |name: &str| {
+		b.borrow_mut().commands.push(Command::OpenDoor(String::from(name)));
+	});
+}
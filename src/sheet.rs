@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::path::Path;
+use serde::Deserialize;
+
+use crate::asset::{TextureHandle, Textures};
+
+// A frame of a sprite sheet, drawn from part of its texture.
+pub struct Frame {
+	// Area of the texture with the frame, x, y, width and height in pixels.
+	pub rect: [f64; 4],
+	// Seconds the frame is shown.
+	pub duration: f64,
+	// Point of the frame drawn on the position of the entity, in pixels from
+	// its top left corner. The center unless a slice gives a pivot.
+	pub pivot: [f64; 2],
+}
+
+// A sprite sheet exported from Aseprite with "Export Sprite Sheet", as a JSON
+// data file (hash or array) next to the image it names. Each tag is a clip
+// named after it, and slices with a pivot set the pivot of their frames.
+pub struct SpriteSheet {
+	pub texture: TextureHandle,
+	pub frames: Vec<Frame>,
+	// Frames of each clip in the order they are played.
+	clips: HashMap<String, Vec<usize>>,
+	// Played for clips the sheet doesn't have, the first tag or every frame.
+	default: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+struct SheetFile {
+	frames: FrameList,
+	meta: Meta,
+}
+
+// Aseprite writes the frames as an array or as an object keyed by file name.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FrameList {
+	Array(Vec<FrameData>),
+	Hash(serde_json::Map<String, serde_json::Value>),
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct Rect {
+	x: f64,
+	y: f64,
+	w: f64,
+	h: f64,
+}
+
+#[derive(Deserialize)]
+struct Point {
+	x: f64,
+	y: f64,
+}
+
+#[derive(Deserialize)]
+struct FrameData {
+	frame: Rect,
+	// In milliseconds.
+	duration: f64,
+}
+
+#[derive(Deserialize)]
+struct Meta {
+	image: String,
+	#[serde(rename = "frameTags", default)]
+	frame_tags: Vec<Tag>,
+	#[serde(default)]
+	slices: Vec<Slice>,
+}
+
+#[derive(Deserialize)]
+struct Tag {
+	name: String,
+	from: usize,
+	to: usize,
+	#[serde(default)]
+	direction: String,
+}
+
+#[derive(Deserialize)]
+struct Slice {
+	keys: Vec<SliceKey>,
+}
+
+// A key applies from its frame until the next key of the slice.
+#[derive(Deserialize)]
+struct SliceKey {
+	frame: usize,
+	bounds: Rect,
+	pivot: Option<Point>,
+}
+
+impl SpriteSheet {
+	pub fn load(path: &str, textures: &mut Textures) -> Result<SpriteSheet, String> {
+		let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+		let file: SheetFile = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+		let data = match file.frames {
+			FrameList::Array(frames) => frames,
+			FrameList::Hash(frames) => frames.into_iter()
+				.map(|(_, frame)| serde_json::from_value(frame))
+				.collect::<Result<_, _>>()
+				.map_err(|e| e.to_string())?,
+		};
+		if data.is_empty() {
+			return Err(String::from("the sheet has no frames"))
+		}
+		let mut frames: Vec<Frame> = data.iter().map(|d| Frame {
+			rect: [d.frame.x, d.frame.y, d.frame.w, d.frame.h],
+			duration: d.duration/1000.,
+			pivot: [d.frame.w/2., d.frame.h/2.],
+		}).collect();
+
+		for slice in &file.meta.slices {
+			for (i, key) in slice.keys.iter().enumerate() {
+				let pivot = match &key.pivot {
+					Some(pivot) => [key.bounds.x+pivot.x, key.bounds.y+pivot.y],
+					None => continue,
+				};
+				let end = slice.keys.get(i+1).map(|k| k.frame).unwrap_or(frames.len()).min(frames.len());
+				for frame in frames.iter_mut().take(end).skip(key.frame) {
+					frame.pivot = pivot;
+				}
+			}
+		}
+
+		let mut clips = HashMap::new();
+		let mut default = None;
+		for tag in &file.meta.frame_tags {
+			if tag.from > tag.to || tag.to >= frames.len() {
+				println!("Tag {:?} in {:?} is out of range", tag.name, path);
+				continue
+			}
+			let forward = tag.from..=tag.to;
+			let clip: Vec<usize> = match tag.direction.as_str() {
+				"reverse" => forward.rev().collect(),
+				// Back and forth without repeating the first and last frame.
+				"pingpong" => forward.clone().chain((tag.from+1..tag.to).rev()).collect(),
+				_ => forward.collect(),
+			};
+			default.get_or_insert_with(|| clip.clone());
+			clips.insert(tag.name.clone(), clip);
+		}
+
+		// The image is relative to the data file.
+		let image = Path::new(path).parent().unwrap_or(Path::new("")).join(&file.meta.image);
+		Ok(SpriteSheet {
+			texture: textures.load(&image.to_string_lossy()),
+			default: default.unwrap_or_else(|| (0..frames.len()).collect()),
+			frames,
+			clips,
+		})
+	}
+	// Frames of the named clip, never empty.
+	pub fn clip(&self, name: &str) -> &[usize] {
+		self.clips.get(name).unwrap_or(&self.default)
+	}
+}
@@ -0,0 +1,135 @@
+use graphics::types::Matrix2d;
+use graphics::triangulation::{tx, ty};
+use image::{Rgba, RgbaImage};
+
+use crate::asset::Textures;
+use crate::render::Renderer;
+
+// Draws frames into an image on the CPU, so they can be rendered without a GPU.
+// Textures are sampled from the nearest pixel and colors blended like the
+// OpenGL backend does.
+pub struct Software {
+	pub image: RgbaImage,
+	// Copies of the atlas pages and the versions they were copied at.
+	pages: Vec<(u32, RgbaImage)>,
+}
+
+impl Software {
+	pub fn new(width: u32, height: u32) -> Software {
+		Software {
+			image: RgbaImage::new(width, height),
+			pages: Vec::new(),
+		}
+	}
+	pub fn save(&self, path: &str) -> Result<(), String> {
+		self.image.save(path).map_err(|e| e.to_string())
+	}
+	// Fill the pixels with their centers inside a triangle, uv gives the
+	// texture coordinates of the corners when it's textured.
+	fn triangle(&mut self, mut xy: [[f64; 2]; 3], mut uv: Option<(usize, [[f32; 2]; 3])>, color: [f32; 4]) {
+		let mut area = edge(xy[0], xy[1], xy[2]);
+		if area == 0. {
+			return
+		}
+		// Mirrored sprites are wound the other way.
+		if area < 0. {
+			xy.swap(1, 2);
+			if let Some((_, uv)) = &mut uv {
+				uv.swap(1, 2);
+			}
+			area = -area;
+		}
+		let (width, height) = self.image.dimensions();
+		let left = xy.iter().map(|p| p[0]).fold(f64::MAX, f64::min).floor().max(0.) as u32;
+		let top = xy.iter().map(|p| p[1]).fold(f64::MAX, f64::min).floor().max(0.) as u32;
+		let right = (xy.iter().map(|p| p[0]).fold(f64::MIN, f64::max).ceil().max(0.) as u32).min(width);
+		let bottom = (xy.iter().map(|p| p[1]).fold(f64::MIN, f64::max).ceil().max(0.) as u32).min(height);
+		for y in top..bottom {
+			for x in left..right {
+				let p = [x as f64+0.5, y as f64+0.5];
+				let weights = [edge(xy[1], xy[2], p), edge(xy[2], xy[0], p), edge(xy[0], xy[1], p)];
+				let edges = [(xy[1], xy[2]), (xy[2], xy[0]), (xy[0], xy[1])];
+				// Pixels on an edge shared by two triangles are only drawn by one of them.
+				if weights.iter().zip(&edges).any(|(&w, &(a, b))| w < 0. || (w == 0. && !top_left(a, b))) {
+					continue
+				}
+				let source = match &uv {
+					Some((page, uv)) => {
+						let (u, v) = (0..3).fold((0., 0.), |(u, v), i| {
+							(u+weights[i]/area*uv[i][0] as f64, v+weights[i]/area*uv[i][1] as f64)
+						});
+						let page = &self.pages[*page].1;
+						let (page_w, page_h) = page.dimensions();
+						let texel = page.get_pixel(((u*page_w as f64) as u32).min(page_w-1), ((v*page_h as f64) as u32).min(page_h-1));
+						let mut source = color;
+						for (c, t) in source.iter_mut().zip(texel.0.iter()) {
+							*c *= *t as f32/255.;
+						}
+						source
+					},
+					None => color,
+				};
+				blend(self.image.get_pixel_mut(x, y), source);
+			}
+		}
+	}
+}
+
+impl Renderer for Software {
+	fn sync(&mut self, textures: &Textures) {
+		for (i, page) in textures.pages().iter().enumerate() {
+			if self.pages.get(i).map(|p| p.0) == Some(page.version) {
+				continue
+			}
+			if i < self.pages.len() {
+				self.pages[i] = (page.version, page.image.clone());
+			}else {
+				self.pages.push((page.version, page.image.clone()));
+			}
+		}
+	}
+	fn clear(&mut self, color: [f32; 4]) {
+		let pixel = Rgba([to_byte(color[0]), to_byte(color[1]), to_byte(color[2]), to_byte(color[3])]);
+		for p in self.image.pixels_mut() {
+			*p = pixel;
+		}
+	}
+	fn rectangle(&mut self, color: [f32; 4], rect: [f64; 4], transform: Matrix2d) {
+		let (x0, y0, x1, y1) = (rect[0], rect[1], rect[0]+rect[2], rect[1]+rect[3]);
+		let corners = [[x0, y0], [x1, y0], [x1, y1], [x0, y1]];
+		let p: Vec<[f64; 2]> = corners.iter().map(|c| [tx(transform, c[0], c[1]) as f64, ty(transform, c[0], c[1]) as f64]).collect();
+		self.triangle([p[0], p[1], p[3]], None, color);
+		self.triangle([p[1], p[2], p[3]], None, color);
+	}
+	fn triangles(&mut self, page: usize, color: [f32; 4], xy: &[[f64; 2]], uv: &[[f32; 2]], transform: Matrix2d) {
+		for (xy, uv) in xy.chunks_exact(3).zip(uv.chunks_exact(3)) {
+			let p = |i: usize| [tx(transform, xy[i][0], xy[i][1]) as f64, ty(transform, xy[i][0], xy[i][1]) as f64];
+			self.triangle([p(0), p(1), p(2)], Some((page, [uv[0], uv[1], uv[2]])), color);
+		}
+	}
+}
+
+// Twice the area of the triangle a, b, p, positive when p is right of a to b
+// with y pointing down.
+fn edge(a: [f64; 2], b: [f64; 2], p: [f64; 2]) -> f64 {
+	(b[0]-a[0])*(p[1]-a[1])-(b[1]-a[1])*(p[0]-a[0])
+}
+
+// Whether an edge of a clockwise triangle is a top or a left edge, the edges
+// pixels exactly on them belong to.
+fn top_left(a: [f64; 2], b: [f64; 2]) -> bool {
+	(a[1] == b[1] && b[0] > a[0]) || b[1] < a[1]
+}
+
+// Draw a color over a pixel by its alpha.
+fn blend(pixel: &mut Rgba<u8>, color: [f32; 4]) {
+	let alpha = color[3];
+	for (channel, color) in pixel.0.iter_mut().zip(&color[..3]) {
+		*channel = to_byte(color*alpha+*channel as f32/255.*(1.-alpha));
+	}
+	pixel.0[3] = to_byte(alpha+pixel.0[3] as f32/255.*(1.-alpha));
+}
+
+fn to_byte(value: f32) -> u8 {
+	(value.clamp(0., 1.)*255.).round() as u8
+}
@@ -0,0 +1,155 @@
+use crate::animator::Flag;
+use crate::collision::{Cube, Rect};
+use crate::ecs::{Entity, Parts, Scene};
+use crate::event::GameEvent;
+use crate::health::Damage;
+use crate::mob::KNOCKBACK;
+
+const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+// What the systems get to work with besides the scene during an update.
+pub struct Frame<'a> {
+	pub world: &'a crate::World,
+	pub collidables: &'a Vec<Cube>,
+	pub keystate: &'a mut crate::Keys,
+	pub camera: &'a mut crate::Camera,
+	pub events: &'a mut Vec<GameEvent>,
+	pub args: &'a piston::UpdateArgs,
+}
+
+pub type System = fn(&mut Scene, &mut Frame);
+
+// Run in this order on every update. A new kind of entity only needs its
+// components, and a system here if none of these move it yet.
+pub const SYSTEMS: [System; 6] = [
+	players,
+	mobs,
+	contact,
+	despawn,
+	animate,
+	camera,
+];
+
+pub fn run(scene: &mut Scene, frame: &mut Frame) {
+	for system in SYSTEMS.iter() {
+		system(scene, frame);
+	}
+}
+
+fn players(scene: &mut Scene, frame: &mut Frame) {
+	for entity in scene.players() {
+		// Players without an animation are moved all the same.
+		if let Some(Parts { mut body, player: Some(player), animation, .. }) = scene.parts(entity) {
+			player.update(&mut body, animation, frame);
+		}
+	}
+}
+
+// Mobs go after the first player.
+fn mobs(scene: &mut Scene, frame: &mut Frame) {
+	let target = scene.players().first().and_then(|p| scene.transforms.get(*p)).map(|t| t.pos);
+	for entity in scene.mobs() {
+		if let Some(Parts { mut body, mob: Some(mob), .. }) = scene.parts(entity) {
+			mob.update(&mut body, frame.world, frame.collidables, target, frame.events, frame.args.dt);
+		}
+	}
+}
+
+// Players stomp entities that hurt on contact by falling on them, and get hurt
+// by touching them otherwise.
+fn contact(scene: &mut Scene, frame: &mut Frame) {
+	let harmful: Vec<(Entity, i32)> = scene.contact_damages.iter().map(|(e, d)| (e, *d)).collect();
+	for player in scene.players() {
+		for &(mob, damage) in &harmful {
+			if mob == player {
+				continue
+			}
+			let (p, m) = match (scene.transforms.get(player), scene.transforms.get(mob)) {
+				(Some(p), Some(m)) => (*p, *m),
+				_ => continue,
+			};
+			let dead = scene.healths.get(mob).map(|h| h.dead()).unwrap_or(false);
+			if dead || !Rect::new(m.pos, m.size).overlaps(&p.pos, &p.size) {
+				continue
+			}
+			frame.events.push(GameEvent::Touch { player, mob });
+			let falling = scene.velocities.get(player).map(|v| v.y > 0.).unwrap_or(false);
+			if falling && p.pos.y < m.pos.y-m.size.y/4. {
+				hurt(scene, mob, &Damage::from(1, &p.pos, &m.pos, KNOCKBACK), frame.events);
+				if let Some(Parts { mut body, player: Some(controller), .. }) = scene.parts(player) {
+					controller.bounce(&mut body);
+				}
+				continue
+			}
+			hurt(scene, player, &Damage::from(damage, &m.pos, &p.pos, KNOCKBACK), frame.events);
+		}
+	}
+}
+
+// Dead players respawn at a checkpoint instead.
+fn despawn(scene: &mut Scene, _frame: &mut Frame) {
+	let dead: Vec<Entity> = scene.healths.iter().filter(|(_, h)| h.dead()).map(|(e, _)| e).collect();
+	for entity in dead {
+		if scene.players.get(entity).is_none() {
+			scene.despawn(entity);
+		}
+	}
+}
+
+// Set the flags read from the components of animated entities, then run their animators.
+fn animate(scene: &mut Scene, frame: &mut Frame) {
+	let (colliders, velocities) = (&scene.colliders, &scene.velocities);
+	for (entity, animation) in scene.animations.iter_mut() {
+		if let Some(collider) = colliders.get(entity) {
+			animation.set(Flag::OnGround, collider.on_ground);
+		}
+		if let Some(velocity) = velocities.get(entity) {
+			animation.set(Flag::Moving, velocity.x.abs() > 0.1);
+			animation.set(Flag::Rising, velocity.y < 0.);
+			animation.set(Flag::Falling, velocity.y > 0.);
+		}
+		let events = &mut *frame.events;
+		animation.update(frame.args.dt, |name| events.push(GameEvent::Animation { entity, name: String::from(name) }));
+	}
+}
+
+// The camera follows the first player, unless a script points it somewhere else.
+fn camera(scene: &mut Scene, frame: &mut Frame) {
+	let camera = &mut *frame.camera;
+	let player = scene.players().first().and_then(|p| scene.transforms.get(*p)).map(|t| t.pos);
+	if let Some(pos) = camera.focus.or(player) {
+		camera.center_on(pos);
+	}
+}
+
+// Hurt an entity the way its controller takes damage, if it has one that does.
+pub fn hurt(scene: &mut Scene, entity: Entity, damage: &Damage, events: &mut Vec<GameEvent>) {
+	match scene.parts(entity) {
+		Some(Parts { mut body, player: Some(player), .. }) => {
+			player.hurt(&mut body, damage, events);
+		},
+		Some(Parts { mut body, .. }) => body.hurt(damage),
+		None => (),
+	}
+}
+
+// Draws every entity with a sprite or an animation, the animated ones on top.
+// Entities flash while they are invulnerable.
+pub fn render(scene: &Scene, batch: &mut crate::render::Batch) {
+	let visible = |entity: Entity| scene.healths.get(entity).map(|h| h.visible()).unwrap_or(true);
+	for (entity, sprite) in scene.sprites.iter() {
+		let texture = match &sprite.texture {
+			Some(texture) if scene.animations.get(entity).is_none() => texture,
+			_ => continue,
+		};
+		if let Some(transform) = scene.transforms.get(entity).filter(|_| visible(entity)) {
+			crate::render::draw_texture(batch, texture, sprite.color, transform);
+		}
+	}
+	for (entity, animation) in scene.animations.iter() {
+		let color = scene.sprites.get(entity).map(|s| s.color).unwrap_or(WHITE);
+		if let Some(transform) = scene.transforms.get(entity).filter(|_| visible(entity)) {
+			crate::render::draw_frame(batch, &animation.sheet, animation.frame(), color, transform);
+		}
+	}
+}
@@ -0,0 +1,42 @@
+use graphics::types::{Color, Matrix2d};
+
+use crate::render::Renderer;
+
+// A tiny 3x5 pixel font drawn with rectangles, so no font files are needed.
+// Every row of a glyph is three bits, the highest bit is the leftmost pixel.
+fn glyph(c: char) -> [u8; 5] {
+	match c.to_ascii_uppercase() {
+		'0' => [7,5,5,5,7], '1' => [2,6,2,2,7], '2' => [7,1,7,4,7], '3' => [7,1,3,1,7],
+		'4' => [5,5,7,1,1], '5' => [7,4,7,1,7], '6' => [7,4,7,5,7], '7' => [7,1,2,2,2],
+		'8' => [7,5,7,5,7], '9' => [7,5,7,1,7],
+		'A' => [2,5,7,5,5], 'B' => [6,5,6,5,6], 'C' => [3,4,4,4,3], 'D' => [6,5,5,5,6],
+		'E' => [7,4,6,4,7], 'F' => [7,4,6,4,4], 'G' => [3,4,5,5,3], 'H' => [5,5,7,5,5],
+		'I' => [7,2,2,2,7], 'J' => [1,1,1,5,2], 'K' => [5,5,6,5,5], 'L' => [4,4,4,4,7],
+		'M' => [5,7,7,5,5], 'N' => [6,5,5,5,5], 'O' => [2,5,5,5,2], 'P' => [6,5,6,4,4],
+		'Q' => [2,5,5,6,3], 'R' => [6,5,6,5,5], 'S' => [3,4,2,1,6], 'T' => [7,2,2,2,2],
+		'U' => [5,5,5,5,7], 'V' => [5,5,5,5,2], 'W' => [5,5,7,7,5], 'X' => [5,5,2,5,5],
+		'Y' => [5,5,2,2,2], 'Z' => [7,1,2,4,7],
+		':' => [0,2,0,2,0], '.' => [0,0,0,0,2], '/' => [1,1,2,4,4], '-' => [0,0,7,0,0],
+		'+' => [0,2,7,2,0],
+		_ => [0,0,0,0,0],
+	}
+}
+
+// Width of the text in pixels, pixel is the size of a single font pixel.
+pub fn width(text: &str, pixel: f64) -> f64 {
+	(text.chars().count()*4) as f64*pixel-pixel
+}
+
+// Draw text with its top left corner at x, y.
+pub fn draw(text: &str, x: f64, y: f64, pixel: f64, color: Color, transform: Matrix2d, renderer: &mut dyn Renderer) {
+	for (i, c) in text.chars().enumerate() {
+		let left = x+(i*4) as f64*pixel;
+		for (row, bits) in glyph(c).iter().enumerate() {
+			for column in 0..3 {
+				if bits & (4 >> column) != 0 {
+					renderer.rectangle(color, [left+column as f64*pixel, y+row as f64*pixel, pixel, pixel], transform);
+				}
+			}
+		}
+	}
+}
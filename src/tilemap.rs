@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use crate::collision::Cube;
+use crate::render::Batch;
+use crate::vector::Vec2;
+use crate::METER_IN_PIXELS;
+
+// Width and height of a chunk in tiles.
+pub const CHUNK_TILES: f64 = 16.;
+
+// The tiles of a square of the map, drawn from a batch built once and kept
+// until its tiles change or the atlas is packed differently. The batch is
+// copied into the batch of every frame the chunk is seen in.
+struct Chunk {
+	// The tiles of every layer in the chunk, later layers are drawn on top.
+	layers: Vec<Vec<Cube>>,
+	// Left, top, right and bottom edges of the tiles in world pixels.
+	bounds: [f64; 4],
+	batch: Batch,
+	dirty: bool,
+}
+
+// The tile layers of the map in chunks, so drawing them only costs as much as
+// the chunks in view. The tilemap keeps its own copy of the tiles to draw,
+// collisions are still checked against the solid tiles of the App.
+pub struct Tilemap {
+	chunks: HashMap<(i64, i64), Chunk>,
+	// Atlas layout the chunks were built with.
+	layout: u32,
+}
+
+impl Tilemap {
+	pub fn new() -> Tilemap {
+		Tilemap {
+			chunks: HashMap::new(),
+			layout: 0,
+		}
+	}
+	pub fn clear(&mut self) {
+		self.chunks.clear();
+	}
+	// Put the tile in the layer, replacing the tile of the layer at the same
+	// position. Its chunk is built again before it's drawn next.
+	pub fn set_tile(&mut self, layer: usize, tile: Cube) {
+		let chunk = self.chunks.entry(chunk_at(tile.pos)).or_insert_with(|| Chunk {
+			layers: Vec::new(),
+			bounds: [0.; 4],
+			batch: Batch::new(),
+			dirty: true,
+		});
+		if chunk.layers.len() <= layer {
+			chunk.layers.resize_with(layer+1, Vec::new);
+		}
+		let tiles = &mut chunk.layers[layer];
+		match tiles.iter_mut().find(|t| t.pos.x == tile.pos.x && t.pos.y == tile.pos.y) {
+			Some(old) => *old = tile,
+			None => tiles.push(tile),
+		}
+		chunk.bounds = bounds(&chunk.layers);
+		chunk.dirty = true;
+	}
+	// Add the chunks the camera sees to the batch.
+	pub fn render(&mut self, textures: &crate::asset::Textures, camera: &crate::Camera, batch: &mut Batch) {
+		if textures.layout() != self.layout {
+			self.layout = textures.layout();
+			for chunk in self.chunks.values_mut() {
+				chunk.dirty = true;
+			}
+		}
+		let [left, top, right, bottom] = crate::render::visible_area(camera);
+		for chunk in self.chunks.values_mut() {
+			let [x0, y0, x1, y1] = chunk.bounds;
+			if y0 > bottom || y1 < top || x0 > right || x1 < left {
+				continue
+			}
+			if chunk.dirty {
+				chunk.batch = Batch::new();
+				for tile in chunk.layers.iter().flatten() {
+					tile.draw(&mut chunk.batch);
+				}
+				chunk.dirty = false;
+			}
+			batch.extend(&chunk.batch);
+		}
+	}
+}
+
+fn chunk_at(pos: Vec2) -> (i64, i64) {
+	let size = CHUNK_TILES*METER_IN_PIXELS;
+	((pos.x/size).floor() as i64, (pos.y/size).floor() as i64)
+}
+
+fn bounds(layers: &[Vec<Cube>]) -> [f64; 4] {
+	layers.iter().flatten().fold([f64::MAX, f64::MAX, f64::MIN, f64::MIN], |b, tile| {
+		let (w, h) = (tile.size.x/2., tile.size.y/2.);
+		[b[0].min(tile.pos.x-w), b[1].min(tile.pos.y-h), b[2].max(tile.pos.x+w), b[3].max(tile.pos.y+h)]
+	})
+}